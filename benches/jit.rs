@@ -0,0 +1,57 @@
+/*!
+Compares the interpreter with the loops compiled by the `jit` feature.
+
+```sh
+cargo bench --features jit
+```
+*/
+use std::{collections::HashMap, time::Instant};
+
+use stack_lang_compiler::{
+    ast,
+    runtime::{self, Context},
+    util::host::BufferedHost,
+};
+
+const PROGRAMS: &[(&str, &str)] = &[
+    (
+        "main.sl",
+        "{ 1 2 3 4 5 6 7 8 9 + + + + + + + + 10 * println } 100000 for",
+    ),
+    ("fibonacci.sl", "2 3 {copy reverse +} 50 for println"),
+    (
+        "arithmetic",
+        "0 {1 + copy 3 * 7 % copy 2 < ! pop pop} 10000000 for println",
+    ),
+    (
+        "floats",
+        "0.0 {0.5 + copy 1.5 * 2.0 / pop} 10000000 for println",
+    ),
+];
+
+fn main() {
+    println!("{:<14} {:>14} {:>14}", "program", "interpreter", "jit");
+
+    for (name, source) in PROGRAMS {
+        let mut stack = Vec::new();
+        ast::fill(source, &mut stack, &mut 1, &mut 1, &mut HashMap::new()).unwrap();
+
+        let interpreter = measure(&stack, false);
+        let jit = measure(&stack, true);
+
+        println!("{name:<14} {interpreter:>14.2?} {jit:>14.2?}");
+    }
+}
+
+fn measure(stack: &[stack_lang_compiler::Stack], jit: bool) -> std::time::Duration {
+    let mut context = Context {
+        host: Box::new(BufferedHost::default()),
+        ..Default::default()
+    };
+    context.jit.enabled = jit;
+
+    let started = Instant::now();
+    runtime::run(stack.to_vec(), &mut Vec::new(), &mut context).unwrap();
+
+    started.elapsed()
+}
@@ -2,7 +2,8 @@ use std::collections::HashMap;
 
 use crate::{
     ast::extract::{self, operation::OperationType},
-    Stack, ValueType,
+    util::error::ParsingError,
+    Effect, Location, Stack, ValueType,
 };
 
 #[test]
@@ -113,6 +114,7 @@ fn test_extract_scope() {
             &mut line_width,
             &mut line_height,
             &mut user_definitions,
+            &mut crate::ast::Imports::default(),
         )
         .unwrap(),
     )));
@@ -120,12 +122,44 @@ fn test_extract_scope() {
     assert_eq!(
         vec![
             Stack::Value(ValueType::Int(4)),
-            Stack::Value(ValueType::Scope(vec![Stack::Operation(OperationType::Mul)])),
+            Stack::Value(ValueType::Scope(vec![
+                Stack::Location(Location {
+                    line: 0,
+                    column: 1,
+                    origin: None
+                }),
+                Stack::Operation(OperationType::Mul)
+            ])),
         ],
         stack
     );
 
-    assert_eq!(current_index, 3);
+    assert_eq!(current_index, 2);
+}
+
+#[test]
+fn test_fill_after_scope_and_comment() {
+    let mut stack = Vec::new();
+    let mut user_definitions = HashMap::new();
+
+    // Nothing right after a closing bracket or the end of a comment is skipped.
+    crate::ast::fill(
+        "{*}2/* a comment */3",
+        &mut stack,
+        &mut 0,
+        &mut 0,
+        &mut user_definitions,
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            Stack::Value(ValueType::Scope(vec![Stack::Operation(OperationType::Mul)])),
+            Stack::Value(ValueType::Int(2)),
+            Stack::Value(ValueType::Int(3)),
+        ],
+        stack
+    );
 }
 
 #[test]
@@ -143,7 +177,7 @@ fn test_register_macro() {
 
     extract::operation::register_macro(&mut stack, &mut user_definitions).unwrap();
 
-    assert!(user_definitions.contains_key(&"double".to_string()));
+    assert!(user_definitions.contains_key("double"));
 
     extract::operation::keyword("double", &mut stack, &mut 0, &mut user_definitions).unwrap();
 
@@ -157,6 +191,122 @@ fn test_register_macro() {
     );
 }
 
+#[test]
+fn test_register_macro_with_effect() {
+    let mut stack = Vec::new();
+    let mut user_definitions = HashMap::new();
+
+    crate::ast::fill(
+        "{2 *} \"double\" ( n -- n ) macro 4 double",
+        &mut stack,
+        &mut 1,
+        &mut 1,
+        &mut user_definitions,
+    )
+    .unwrap();
+    crate::ast::strip_locations(&mut stack);
+
+    let effect = Effect {
+        name: "double".to_string(),
+        inputs: vec!["n".to_string()],
+        outputs: vec!["n".to_string()],
+    };
+    assert_eq!(effect.to_string(), "( n -- n )");
+    assert_eq!(
+        vec![
+            Stack::Value(ValueType::Int(4)),
+            Stack::Effect(effect.clone()),
+            Stack::Value(ValueType::Int(2)),
+            Stack::Operation(OperationType::Mul),
+            Stack::EffectEnd(effect),
+        ],
+        stack
+    );
+}
+
+#[test]
+fn test_register_macro_with_wrong_effect() {
+    let register = |src: &str| {
+        crate::ast::fill(src, &mut Vec::new(), &mut 1, &mut 1, &mut HashMap::new()).unwrap_err()
+    };
+
+    assert!(matches!(
+        register("{2 * +} \"double\" ( n -- n ) macro"),
+        ParsingError::RegistrationError { reason, .. } if reason.contains("`+` needs 2 value(s)")
+    ));
+    assert!(matches!(
+        register("{copy} \"double\" ( n -- n ) macro"),
+        ParsingError::RegistrationError { reason, .. }
+            if reason == "The body leaves 2 value(s) but the declared stack effect ( n -- n ) leaves 1."
+    ));
+    assert!(matches!(
+        register("{2 *} \"double\" ( n n ) macro"),
+        ParsingError::ExtractionError { .. }
+    ));
+    assert!(matches!(
+        register("1 ( n -- n ) 2"),
+        ParsingError::ExtractionError { .. }
+    ));
+}
+
+#[test]
+fn test_bind_local() {
+    let mut stack = Vec::new();
+
+    crate::ast::fill("2 -> x\tx", &mut stack, &mut 1, &mut 1, &mut HashMap::new()).unwrap();
+    crate::ast::strip_locations(&mut stack);
+
+    assert_eq!(
+        vec![
+            Stack::Value(ValueType::Int(2)),
+            Stack::Value(ValueType::Text("x".to_string())),
+            Stack::Operation(OperationType::LocalSet),
+            Stack::Value(ValueType::Text("x".to_string())),
+            Stack::Operation(OperationType::LocalGet),
+        ],
+        stack
+    );
+
+    for src in [
+        "1 ->",
+        "1 -> if",
+        "1 -> true",
+        "1 -> 2",
+        "1 \"m\" const 2 -> m",
+    ] {
+        assert!(
+            matches!(
+                crate::ast::fill(src, &mut Vec::new(), &mut 1, &mut 1, &mut HashMap::new()),
+                Err(ParsingError::RegistrationError { .. })
+            ),
+            "{src}"
+        );
+    }
+
+    // Names bound in a scope are only known until it closes, names bound outside of it stay.
+    let mut user_definitions = HashMap::new();
+    crate::ast::fill(
+        "1 -> outer { outer 2 -> inner inner } true if outer",
+        &mut Vec::new(),
+        &mut 1,
+        &mut 1,
+        &mut user_definitions,
+    )
+    .unwrap();
+    assert!(user_definitions.contains_key("outer"));
+    assert!(!user_definitions.contains_key("inner"));
+    assert!(matches!(
+        crate::ast::fill(
+            "{ 5 -> inner } true if inner",
+            &mut Vec::new(),
+            &mut 1,
+            &mut 1,
+            &mut HashMap::new()
+        ),
+        Err(ParsingError::KeywordError { .. })
+    ));
+}
+
 #[test]
 fn test_register_constant() {
     let mut stack = vec![
@@ -169,7 +319,7 @@ fn test_register_constant() {
 
     extract::operation::register_constant(&mut stack, &mut user_definitions).unwrap();
 
-    assert!(user_definitions.contains_key(&"FIVE".to_string()));
+    assert!(user_definitions.contains_key("FIVE"));
 
     extract::operation::keyword("FIVE", &mut stack, &mut 0, &mut user_definitions).unwrap();
 
@@ -181,3 +331,177 @@ fn test_register_constant() {
         stack
     );
 }
+
+#[test]
+fn test_fill_locations() {
+    let mut stack = Vec::new();
+    let mut user_definitions = HashMap::new();
+
+    crate::ast::fill(
+        "/* a\ncomment */ 2 {\n\t3 *\n} \"triple\" macro\n1 triple",
+        &mut stack,
+        &mut 1,
+        &mut 1,
+        &mut user_definitions,
+    )
+    .unwrap();
+
+    let location = |line, column, origin: Option<&str>| {
+        Stack::Location(Location {
+            line,
+            column,
+            origin: origin.map(str::to_string),
+        })
+    };
+
+    assert_eq!(
+        vec![
+            Stack::Value(ValueType::Int(2)),
+            Stack::Value(ValueType::Int(1)),
+            location(5, 3, Some("triple")),
+            Stack::Value(ValueType::Int(3)),
+            location(3, 4, Some("triple")),
+            Stack::Operation(OperationType::Mul),
+        ],
+        stack
+    );
+
+    crate::ast::strip_locations(&mut stack);
+
+    assert_eq!(
+        vec![
+            Stack::Value(ValueType::Int(2)),
+            Stack::Value(ValueType::Int(1)),
+            Stack::Value(ValueType::Int(3)),
+            Stack::Operation(OperationType::Mul),
+        ],
+        stack
+    );
+}
+
+#[test]
+fn test_locations_after_scope() {
+    let mut stack = Vec::new();
+
+    crate::ast::fill(
+        "{ 3 * } 2 for\n{\n} true if",
+        &mut stack,
+        &mut 1,
+        &mut 1,
+        &mut HashMap::new(),
+    )
+    .unwrap();
+
+    let columns: Vec<(u32, u32)> = stack
+        .iter()
+        .filter_map(|element| match element {
+            Stack::Location(location) => Some((location.line, location.column)),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(columns, [(1, 11), (3, 8)]);
+}
+
+#[test]
+fn test_scope_formatting_without_locations() {
+    let scope = ValueType::Scope(vec![
+        Stack::Location(Location {
+            line: 1,
+            column: 2,
+            origin: None,
+        }),
+        Stack::Operation(OperationType::Add),
+    ]);
+
+    assert_eq!(format!("{scope}"), "{\n\tOperation(Add)\n}\n");
+    assert_eq!(format!("{scope:?}"), "Scope([Operation(Add)])");
+}
+
+#[test]
+fn test_scope_equality_without_locations() {
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    crate::ast::fill("{ 1 + }", &mut first, &mut 1, &mut 1, &mut HashMap::new()).unwrap();
+    crate::ast::fill(
+        "\n\t{ 1 + }",
+        &mut second,
+        &mut 1,
+        &mut 1,
+        &mut HashMap::new(),
+    )
+    .unwrap();
+
+    // The scopes hold different locations, yet they are the same value.
+    let (Stack::Value(ValueType::Scope(first_scope)), Stack::Value(ValueType::Scope(second_scope))) =
+        (&first[0], &second[0])
+    else {
+        panic!("Expected scopes.");
+    };
+    assert_ne!(first_scope, second_scope);
+    assert_eq!(first, second);
+    assert_ne!(
+        ValueType::Scope(vec![Stack::Operation(OperationType::Add)]),
+        ValueType::Scope(vec![Stack::Operation(OperationType::Sub)])
+    );
+}
+
+#[test]
+fn test_import() {
+    // Unique to the process, so runs at the same time don't share the directory.
+    let root = std::env::temp_dir().join(format!("stack_lang_test_import_{}", std::process::id()));
+    std::fs::create_dir_all(root.join("lib")).unwrap();
+    std::fs::write(
+        root.join("lib/math.sl"),
+        "\"util.sl\" import\n{ copy * } \"square\" macro\n2 \"TWO\" const",
+    )
+    .unwrap();
+    std::fs::write(root.join("lib/util.sl"), "{ 1 + } \"inc\" macro").unwrap();
+    std::fs::write(root.join("cycle.sl"), "\"main.sl\" import").unwrap();
+
+    let parse = |src: &str| {
+        let mut stack = Vec::new();
+        let mut user_definitions = HashMap::new();
+        crate::ast::fill_with_imports(
+            src,
+            &mut stack,
+            &mut 1,
+            &mut 1,
+            &mut user_definitions,
+            &mut crate::ast::Imports::new(root.join("main.sl")),
+        )
+        .map(|_| {
+            crate::ast::strip_locations(&mut stack);
+            stack
+        })
+    };
+
+    let expanded = vec![
+        Stack::Value(ValueType::Int(2)),
+        Stack::Operation(OperationType::Copy),
+        Stack::Operation(OperationType::Mul),
+        Stack::Value(ValueType::Int(1)),
+        Stack::Operation(OperationType::Add),
+    ];
+    assert_eq!(
+        parse("\"lib/math.sl\" import TWO square inc").unwrap(),
+        expanded
+    );
+    assert_eq!(
+        parse("\"lib/math.sl\" \"math\" import_as math.TWO math.square math.inc").unwrap(),
+        expanded
+    );
+    assert!(matches!(
+        parse("\"lib/math.sl\" \"math\" import_as square"),
+        Err(ParsingError::KeywordError { .. })
+    ));
+    assert!(matches!(
+        parse("\"cycle.sl\" import"),
+        Err(ParsingError::InImport { error, .. })
+            if matches!(*error, ParsingError::ImportFailed { ref reason, .. } if reason.starts_with("Import cycle"))
+    ));
+    assert!(matches!(
+        parse("\"missing.sl\" import"),
+        Err(ParsingError::ImportFailed { path, .. }) if path.ends_with("missing.sl")
+    ));
+}
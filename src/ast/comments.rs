@@ -7,7 +7,7 @@ pub fn skip_multiline(src: &str, i: &mut usize, line_height: &mut usize, line_wi
         *line_width = 0;
     }
 
-    *i += comment_end + 2;
+    *i += comment_end + 1;
 }
 
 pub fn skip_singleline(src: &str, i: &mut usize) {
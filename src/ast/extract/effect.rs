@@ -0,0 +1,49 @@
+use crate::{util::error, Effect, Stack};
+
+/**
+Extracts a stack effect declaration like `( a b -- sum )`.
+
+It has to fit on one line and be followed by `macro`, which stores it with the macro.
+*/
+pub fn effect(src: &str, stack: &mut Vec<Stack>, i: &mut usize) -> error::parsing::Result<()> {
+    let failed = |reason: &str| error::ParsingError::ExtractionError {
+        what: "Stack effect".to_string(),
+        reason: reason.to_string(),
+    };
+
+    let end = src
+        .find([')', '\n'])
+        .filter(|end| src[*end..].starts_with(')'))
+        .ok_or_else(|| failed("Could not find `)` on the same line."))?;
+
+    let words: Vec<&str> = src[1..end].split_whitespace().collect();
+    let separator = words
+        .iter()
+        .position(|word| *word == "--")
+        .ok_or_else(|| failed("Inputs and outputs have to be separated with `--`."))?;
+    if words[separator + 1..].contains(&"--") {
+        return Err(failed("`--` can only appear once."));
+    }
+
+    if src[end + 1..].split_whitespace().next() != Some("macro") {
+        return Err(failed(
+            "Stack effects can only be declared right before `macro`.",
+        ));
+    }
+
+    stack.push(Stack::Effect(Effect {
+        name: String::new(),
+        inputs: words[..separator]
+            .iter()
+            .map(|word| word.to_string())
+            .collect(),
+        outputs: words[separator + 1..]
+            .iter()
+            .map(|word| word.to_string())
+            .collect(),
+    }));
+
+    *i += end;
+
+    Ok(())
+}
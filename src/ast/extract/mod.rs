@@ -1,2 +1,3 @@
+pub mod effect;
 pub mod operation;
 pub mod value;
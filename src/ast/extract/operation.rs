@@ -1,7 +1,7 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
-use crate::{util::error, Stack, ValueType};
+use crate::{util::error, Effect, Stack, ValueType};
 
 #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum OperationType {
@@ -32,6 +32,24 @@ pub enum OperationType {
     Println,
     Macro,
     Const,
+    FileRead,
+    FileWrite,
+    FileAppend,
+    FileExists,
+    DirList,
+    LocalSet,
+    LocalGet,
+    Set,
+    Get,
+    PrintDebugVars,
+    Import,
+    ImportAs,
+    Try,
+    Throw,
+    Assert,
+    AssertEq,
+    AssertStack,
+    Test,
 }
 
 lazy_static! {
@@ -49,6 +67,8 @@ lazy_static! {
         ("if", OperationType::If),
         ("for", OperationType::For),
         ("while", OperationType::While),
+        ("try", OperationType::Try),
+        ("throw", OperationType::Throw),
         // conditions
         ("<", OperationType::Lt),
         (">", OperationType::Gt),
@@ -63,6 +83,7 @@ lazy_static! {
         ("println", OperationType::Println),
         ("print_debug", OperationType::PrintDebug),
         ("print_debug_stack", OperationType::PrintDebugStack),
+        ("print_debug_vars", OperationType::PrintDebugVars),
         // stack
         ("switch", OperationType::Switch),
         ("reverse", OperationType::Reverse),
@@ -71,6 +92,22 @@ lazy_static! {
         // register
         ("macro", OperationType::Macro),
         ("const", OperationType::Const),
+        ("import", OperationType::Import),
+        ("import_as", OperationType::ImportAs),
+        // file system
+        ("file_read", OperationType::FileRead),
+        ("file_write", OperationType::FileWrite),
+        ("file_append", OperationType::FileAppend),
+        ("file_exists", OperationType::FileExists),
+        ("dir_list", OperationType::DirList),
+        // testing
+        ("assert", OperationType::Assert),
+        ("assert_eq", OperationType::AssertEq),
+        ("assert_stack", OperationType::AssertStack),
+        ("test", OperationType::Test),
+        // variables
+        ("set", OperationType::Set),
+        ("get", OperationType::Get),
     ]);
     static ref KEYWORDS: HashMap<&'static str, ValueType> = HashMap::from([
         ("true", ValueType::Bool(true)),
@@ -78,18 +115,40 @@ lazy_static! {
     ]);
 }
 
+impl OperationType {
+    /**
+    Keyword that is parsed into this operation.
+
+    Locals have no keyword of their own, they are parsed from `-> name` and from the bound name,
+    which reads the local.
+    */
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            OperationType::LocalSet => "->",
+            OperationType::LocalGet => "local",
+            operation => OPERANDS
+                .iter()
+                .find(|(_, candidate)| *candidate == operation)
+                .map(|(keyword, _)| *keyword)
+                .expect("Every operation has a keyword."),
+        }
+    }
+}
+
 pub fn keyword(
     src: &str,
     stack: &mut Vec<Stack>,
     i: &mut usize,
     user_definitions: &mut crate::HashMap<String, Vec<Stack>>,
 ) -> error::parsing::Result<()> {
-    let presumable_keyword_index = src
-        .find(|c| c == ' ' || c == '\r' || c == '\n')
-        .unwrap_or(src.len());
+    let presumable_keyword_index = src.find([' ', '\r', '\n']).unwrap_or(src.len());
     *i += presumable_keyword_index - 1;
     let presumable_keyword = &src[..presumable_keyword_index];
 
+    if presumable_keyword == "->" {
+        return bind_local(&src[presumable_keyword_index..], stack, i, user_definitions);
+    }
+
     if let Some(operation_type) = OPERANDS.get(&presumable_keyword) {
         if *operation_type == OperationType::Macro {
             register_macro(stack, user_definitions)?;
@@ -99,7 +158,9 @@ pub fn keyword(
             stack.push(Stack::Operation(*operation_type));
         }
     } else if let Some(function) = user_definitions.get(&presumable_keyword.to_string()) {
+        let start = stack.len();
         stack.extend_from_slice(function);
+        mark_origin(&mut stack[start..], presumable_keyword);
     } else if let Some(value) = KEYWORDS.get(&presumable_keyword) {
         // I have to copy here because. The keyword is a heap allocated variable that I'm putting on a stack.
         stack.push(Stack::Value(value.clone()));
@@ -112,10 +173,106 @@ pub fn keyword(
     Ok(())
 }
 
+/**
+Parses the name after `->` into `"name" local_set`.
+
+From then on the name is parsed into `"name" local_get` until the scope it was bound in closes,
+the value is looked up at runtime.
+*/
+fn bind_local(
+    src: &str,
+    stack: &mut Vec<Stack>,
+    i: &mut usize,
+    user_definitions: &mut crate::HashMap<String, Vec<Stack>>,
+) -> error::parsing::Result<()> {
+    let start = src.len() - src.trim_start_matches([' ', '\t', '\r']).len();
+    let end = src[start..]
+        .find([' ', '\t', '\r', '\n'])
+        .map_or(src.len(), |end| start + end);
+    let name = &src[start..end];
+
+    let failed = |reason: String| error::ParsingError::RegistrationError {
+        what: "Local".to_string(),
+        reason,
+    };
+    if name.is_empty() {
+        return Err(failed("Expected a name after `->`.".to_string()));
+    }
+    if OPERANDS.contains_key(name)
+        || KEYWORDS.contains_key(name)
+        || name == "->"
+        || name.starts_with(['{', '}', '(', '"', '\''])
+        || crate::util::parsing::looks_like_number(name)
+    {
+        return Err(failed(format!("`{name}` can't be used as a name.")));
+    }
+    if let Some(definition) = user_definitions.get(name) {
+        if !is_local(definition) {
+            return Err(failed(format!(
+                "`{name}` is already a macro or a constant."
+            )));
+        }
+    }
+
+    stack.push(Stack::Value(ValueType::Text(name.to_string())));
+    stack.push(Stack::Operation(OperationType::LocalSet));
+    user_definitions.insert(
+        name.to_string(),
+        vec![
+            Stack::Value(ValueType::Text(name.to_string())),
+            Stack::Operation(OperationType::LocalGet),
+        ],
+    );
+
+    *i += end;
+
+    Ok(())
+}
+
+/// Tells whether the definition was made by `->` rather than by `macro` or `const`.
+pub fn is_local(definition: &[Stack]) -> bool {
+    matches!(
+        definition,
+        [
+            Stack::Value(ValueType::Text(_)),
+            Stack::Operation(OperationType::LocalGet)
+        ]
+    )
+}
+
+/**
+Marks locations inside of an expanded macro with its name, unless they come from a macro nested in it.
+*/
+fn mark_origin(stack: &mut [Stack], name: &str) {
+    for element in stack {
+        match element {
+            Stack::Location(location) if location.origin.is_none() => {
+                location.origin = Some(name.to_string())
+            }
+            Stack::Value(ValueType::Scope(scope)) => mark_origin(scope, name),
+            _ => (),
+        }
+    }
+}
+
+/**
+Registers the scope under the name, both popped from the stack.
+
+A stack effect declared right before `macro` is verified against the body where it's possible
+and stored around it, so every expansion is checked at runtime.
+*/
 pub fn register_macro(
     stack: &mut Vec<Stack>,
     user_definitions: &mut HashMap<String, Vec<Stack>>,
 ) -> error::parsing::Result<()> {
+    let effect = match stack.last() {
+        Some(Stack::Effect(_)) => match stack.pop() {
+            Some(Stack::Effect(effect)) => Some(effect),
+            _ => unreachable!(),
+        },
+        _ => None,
+    };
+
     if stack.len() < 2 {
         return Err(error::ParsingError::RegistrationError {
             what: "Macro".to_string(),
@@ -126,6 +283,12 @@ pub fn register_macro(
     match stack.pop().unwrap() {
         Stack::Value(ValueType::Text(name)) => match stack.pop().unwrap() {
             Stack::Value(ValueType::Scope(contents)) => {
+                let contents = with_local_frame(contents);
+                let contents = match effect {
+                    Some(effect) => with_effect(&name, effect, contents)?,
+                    None => contents,
+                };
+
                 user_definitions.insert(name, contents);
                 Ok(())
             }
@@ -141,6 +304,50 @@ pub fn register_macro(
     }
 }
 
+/// Gives the macro locals of its own when it binds any, so it can't change the caller's.
+fn with_local_frame(contents: Vec<Stack>) -> Vec<Stack> {
+    if !binds_locals(&contents) {
+        return contents;
+    }
+
+    let mut wrapped = Vec::with_capacity(contents.len() + 2);
+    wrapped.push(Stack::LocalFrame);
+    wrapped.extend(contents);
+    wrapped.push(Stack::LocalFrameEnd);
+
+    wrapped
+}
+
+fn binds_locals(stack: &[Stack]) -> bool {
+    stack.iter().any(|element| match element {
+        Stack::Operation(OperationType::LocalSet) => true,
+        Stack::Value(ValueType::Scope(scope)) => binds_locals(scope),
+        _ => false,
+    })
+}
+
+fn with_effect(
+    name: &str,
+    mut effect: Effect,
+    contents: Vec<Stack>,
+) -> error::parsing::Result<Vec<Stack>> {
+    effect.name = name.to_string();
+
+    if let Err(err) = crate::check::verify_effect(&contents, &effect) {
+        return Err(error::ParsingError::RegistrationError {
+            what: format!("macro `{name}`"),
+            reason: err.to_string().trim_start().to_string(),
+        });
+    }
+
+    let mut wrapped = Vec::with_capacity(contents.len() + 2);
+    wrapped.push(Stack::Effect(effect.clone()));
+    wrapped.extend(contents);
+    wrapped.push(Stack::EffectEnd(effect));
+
+    Ok(wrapped)
+}
+
 pub fn register_constant(
     stack: &mut Vec<Stack>,
     user_definitions: &mut HashMap<String, Vec<Stack>>,
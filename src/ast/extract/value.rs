@@ -1,8 +1,12 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+};
 
+use super::operation;
 use crate::{util::*, Stack};
 
-#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub enum ValueType {
     Int(i64),
     Float(f64),
@@ -21,6 +25,28 @@ impl ValueType {
             ValueType::Bool(condition) => *condition,
         }
     }
+
+    /// Approximate number of bytes the value takes, including what it points to.
+    pub fn memory(&self) -> usize {
+        std::mem::size_of::<ValueType>() + self.heap_memory()
+    }
+
+    fn heap_memory(&self) -> usize {
+        match self {
+            ValueType::Text(text) => text.capacity(),
+            ValueType::Scope(scope) => {
+                scope.capacity() * std::mem::size_of::<Stack>()
+                    + scope
+                        .iter()
+                        .map(|elem| match elem {
+                            Stack::Value(value) => value.heap_memory(),
+                            _ => 0,
+                        })
+                        .sum::<usize>()
+            }
+            ValueType::Int(_) | ValueType::Float(_) | ValueType::Bool(_) => 0,
+        }
+    }
 }
 
 impl Display for ValueType {
@@ -31,7 +57,7 @@ impl Display for ValueType {
             Self::Text(text) => write!(f, "{}", text)?,
             Self::Scope(scope) => {
                 writeln!(f, "{{")?;
-                for elem in scope {
+                for elem in without_locations(scope) {
                     writeln!(f, "\t{:?}", elem)?;
                 }
                 writeln!(f, "}}")?;
@@ -43,6 +69,64 @@ impl Display for ValueType {
     }
 }
 
+/**
+Same as derived `Debug`, except that source locations and boundaries of expanded macros are left
+out of scopes.
+
+That way programs print the same whether they were built with debug information or not.
+*/
+impl Debug for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(int) => f.debug_tuple("Int").field(int).finish(),
+            Self::Float(float) => f.debug_tuple("Float").field(float).finish(),
+            Self::Text(text) => f.debug_tuple("Text").field(text).finish(),
+            Self::Scope(scope) => f.debug_tuple("Scope").field(&ScopeContents(scope)).finish(),
+            Self::Bool(condition) => f.debug_tuple("Bool").field(condition).finish(),
+        }
+    }
+}
+
+/**
+Same as derived `PartialEq`, except that source locations and boundaries of expanded macros in
+scopes are ignored.
+
+That way the same scope is equal wherever it was written in the source.
+*/
+impl PartialEq for ValueType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a == b,
+            (Self::Text(a), Self::Text(b)) => a == b,
+            (Self::Scope(a), Self::Scope(b)) => without_locations(a).eq(without_locations(b)),
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+struct ScopeContents<'a>(&'a [Stack]);
+
+impl Debug for ScopeContents<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(without_locations(self.0)).finish()
+    }
+}
+
+fn without_locations(scope: &[Stack]) -> impl Iterator<Item = &Stack> {
+    scope.iter().filter(|elem| {
+        !matches!(
+            elem,
+            Stack::Location(_)
+                | Stack::Effect(_)
+                | Stack::EffectEnd(_)
+                | Stack::LocalFrame
+                | Stack::LocalFrameEnd
+        )
+    })
+}
+
 pub fn number(src: &str, stack: &mut Vec<Stack>, i: &mut usize) -> error::parsing::Result<()> {
     let mut num = String::with_capacity(40);
     let mut index = 0;
@@ -94,7 +178,7 @@ pub fn string(
     line_height: &mut usize,
     line_width: &mut usize,
 ) -> error::parsing::Result<()> {
-    let word_end = if let Some(end) = src[1..].find(|ch| ch == '\"' || ch == '\'') {
+    let word_end = if let Some(end) = src[1..].find(['\"', '\'']) {
         end
     } else {
         return Err(error::ParsingError::ExtractionError {
@@ -128,19 +212,32 @@ pub fn scope(
     line_width: &mut usize,
     line_height: &mut usize,
     user_definitions: &mut HashMap<String, Vec<Stack>>,
+    imports: &mut crate::ast::Imports,
 ) -> error::parsing::Result<Vec<Stack>> {
     let scope_end = parsing::find_closing_bracket(&src[1..]);
 
     let mut scopes_stack: Vec<Stack> = Vec::new();
-    crate::ast::fill(
+    let outer_locals: HashSet<String> = user_definitions
+        .iter()
+        .filter(|(_, definition)| operation::is_local(definition))
+        .map(|(name, _)| name.clone())
+        .collect();
+    // Contents start right after the opening bracket.
+    *line_width += 1;
+    crate::ast::fill_with_imports(
         &src[1..scope_end],
         &mut scopes_stack,
         line_height,
         line_width,
         user_definitions,
+        imports,
     )?;
 
-    *i += scope_end + 1;
+    // Locals bound in the scope are dropped with it, their names mean nothing after it.
+    user_definitions
+        .retain(|name, definition| !operation::is_local(definition) || outer_locals.contains(name));
+
+    *i += scope_end;
 
     Ok(scopes_stack)
 }
@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{extract::operation::is_local, fill_with_imports, strip_locations},
+    util::error,
+    Stack, ValueType,
+};
+
+/**
+Files that are being parsed, each one imported by the one before it.
+
+Paths of imports are relative to the directory of the last one.
+*/
+#[derive(Debug, Default)]
+pub struct Imports {
+    files: Vec<PathBuf>,
+    /// Only the prelude modules can be imported, no file is read.
+    prelude_only: bool,
+}
+
+impl Imports {
+    /// Parsing starts in the file.
+    pub fn new(file: PathBuf) -> Self {
+        Imports {
+            files: vec![file],
+            prelude_only: false,
+        }
+    }
+
+    /// Imports that never touch the file system.
+    pub fn prelude_only() -> Self {
+        Imports {
+            files: Vec::new(),
+            prelude_only: true,
+        }
+    }
+
+    fn directory(&self) -> &Path {
+        self.files
+            .last()
+            .and_then(|file| file.parent())
+            .unwrap_or(Path::new(""))
+    }
+}
+
+/**
+Parses the file named by the Text on the stack and merges its macros and constants.
+Names of the prelude modules, like `std/math`, are imported from the interpreter instead of a file.
+
+`import_as` also pops a namespace from above the path and prefixes every imported name with it,
+like `math.square`. Imported files can only define macros and constants, they can't run code.
+Locations are left out of imported definitions, so runtime errors point at where they are used.
+*/
+pub fn import(
+    stack: &mut Vec<Stack>,
+    user_definitions: &mut HashMap<String, Vec<Stack>>,
+    imports: &mut Imports,
+    namespaced: bool,
+) -> error::parsing::Result<()> {
+    let namespace = if namespaced {
+        let namespace = pop_text(stack, "namespace")?;
+        if namespace.is_empty() || namespace.contains(char::is_whitespace) {
+            return Err(error::ParsingError::RegistrationError {
+                what: "Import".to_string(),
+                reason: format!("`{namespace}` can't be used as a namespace."),
+            });
+        }
+        Some(namespace)
+    } else {
+        None
+    };
+    let name = pop_text(stack, "path")?;
+    let prelude = crate::prelude::module(&name);
+    let path = match prelude {
+        Some(_) => PathBuf::from(name),
+        None => imports.directory().join(name),
+    };
+
+    let failed = |reason: String| error::ParsingError::ImportFailed {
+        path: path.display().to_string(),
+        reason,
+    };
+
+    if prelude.is_none() && imports.prelude_only {
+        return Err(failed(
+            "Only the prelude modules can be imported here.".to_string(),
+        ));
+    }
+    if imports.files.iter().any(|file| same_file(file, &path)) {
+        let cycle: Vec<String> = imports
+            .files
+            .iter()
+            .chain([&path])
+            .map(|file| file.display().to_string())
+            .collect();
+        return Err(failed(format!("Import cycle: {}.", cycle.join(" -> "))));
+    }
+
+    let src = match prelude {
+        Some(src) => src.to_string(),
+        None => std::fs::read_to_string(&path).map_err(|err| failed(err.to_string()))?,
+    };
+
+    let mut imported_stack = Vec::new();
+    let mut definitions = HashMap::new();
+    let mut line_height = 1;
+    let mut line_width = 1;
+    imports.files.push(path.clone());
+    let parsed = fill_with_imports(
+        &src,
+        &mut imported_stack,
+        &mut line_height,
+        &mut line_width,
+        &mut definitions,
+        imports,
+    );
+    imports.files.pop();
+
+    if let Err(err) = parsed {
+        return Err(error::ParsingError::InImport {
+            path: path.display().to_string(),
+            line: line_height,
+            column: line_width,
+            error: Box::new(err),
+        });
+    }
+    if !imported_stack.is_empty() {
+        return Err(failed(
+            "Imported files can only define macros and constants.".to_string(),
+        ));
+    }
+
+    for (name, mut definition) in definitions {
+        if is_local(&definition) {
+            continue;
+        }
+
+        strip_locations(&mut definition);
+        let name = match &namespace {
+            Some(namespace) => {
+                prefix_effects(&mut definition, namespace);
+                format!("{namespace}.{name}")
+            }
+            None => name,
+        };
+
+        user_definitions.insert(name, definition);
+    }
+
+    Ok(())
+}
+
+fn pop_text(stack: &mut Vec<Stack>, what: &str) -> error::parsing::Result<String> {
+    match stack.pop() {
+        Some(Stack::Value(ValueType::Text(text))) => Ok(text),
+        Some(element) => Err(error::ParsingError::MismatchedTypes {
+            expected: format!("Text with the {what}"),
+            got: format!("{:?}", element),
+        }),
+        None => Err(error::ParsingError::RegistrationError {
+            what: "Import".to_string(),
+            reason: "Not enough arguments.".to_string(),
+        }),
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Renames stack effects of the imported macros, including the ones expanded in them.
+fn prefix_effects(stack: &mut [Stack], namespace: &str) {
+    for element in stack {
+        match element {
+            Stack::Effect(effect) | Stack::EffectEnd(effect) => {
+                effect.name = format!("{namespace}.{}", effect.name)
+            }
+            Stack::Value(ValueType::Scope(scope)) => prefix_effects(scope, namespace),
+            _ => (),
+        }
+    }
+}
@@ -2,9 +2,11 @@
 mod ast_test;
 mod comments;
 pub mod extract;
+mod import;
 
-use crate::{util::*, Stack};
+use crate::{util::*, Location, Stack};
 pub use extract::{operation::OperationType, value::ValueType};
+pub use import::Imports;
 use std::collections::HashMap;
 
 pub fn fill(
@@ -13,6 +15,27 @@ pub fn fill(
     line_height: &mut usize,
     line_width: &mut usize,
     user_definitions: &mut HashMap<String, Vec<Stack>>,
+) -> error::parsing::Result<()> {
+    fill_with_imports(
+        src,
+        stack,
+        line_height,
+        line_width,
+        user_definitions,
+        &mut Imports::default(),
+    )
+}
+
+/**
+Same as `fill`, paths of imports are relative to the file that is parsed last in `imports`.
+*/
+pub fn fill_with_imports(
+    src: &str,
+    stack: &mut Vec<Stack>,
+    line_height: &mut usize,
+    line_width: &mut usize,
+    user_definitions: &mut HashMap<String, Vec<Stack>>,
+    imports: &mut Imports,
 ) -> error::parsing::Result<()> {
     let chars: Vec<char> = src.chars().collect();
     let mut i = 0;
@@ -20,6 +43,12 @@ pub fn fill(
     while i < chars.len() {
         let ch = chars[i];
         let old_i = i;
+        let old_len = stack.len();
+        let location = Location {
+            line: *line_height as u32,
+            column: *line_width as u32,
+            origin: None,
+        };
 
         match ch {
             ' ' => (),
@@ -52,20 +81,73 @@ pub fn fill(
                 extract::value::string(&src[i..], stack, &mut i, line_height, line_width)?
             }
 
+            '(' => extract::effect::effect(&src[i..], stack, &mut i)?,
+
             '{' => stack.push(Stack::Value(ValueType::Scope(extract::value::scope(
                 &src[i..],
                 &mut i,
                 line_width,
                 line_height,
                 user_definitions,
+                imports,
             )?))),
 
-            _ => extract::operation::keyword(&src[i..], stack, &mut i, user_definitions)?,
+            _ => {
+                extract::operation::keyword(&src[i..], stack, &mut i, user_definitions)?;
+
+                if let Some(Stack::Operation(
+                    operation @ (OperationType::Import | OperationType::ImportAs),
+                )) = stack.last()
+                {
+                    let namespaced = *operation == OperationType::ImportAs;
+                    stack.pop();
+                    import::import(stack, user_definitions, imports, namespaced)?;
+                }
+            }
         };
 
-        *line_width += (i - old_i) + 1;
+        // Constants and macros are marked even though constants expand only to a value.
+        let word: String = chars[old_i..(i + 1).min(chars.len())].iter().collect();
+        let expanded = user_definitions
+            .get(&word)
+            .is_some_and(|definition| !extract::operation::is_local(definition));
+        if stack.len() > old_len
+            && (expanded
+                || stack[old_len..]
+                    .iter()
+                    .any(|element| matches!(element, Stack::Operation(_))))
+        {
+            stack.insert(
+                old_len,
+                Stack::Location(Location {
+                    origin: expanded.then_some(word),
+                    ..location
+                }),
+            );
+        }
+
+        let consumed = &chars[old_i..(i + 1).min(chars.len())];
+        match consumed.iter().rposition(|ch| *ch == '\n') {
+            // The contents of a scope already moved the position to its closing bracket.
+            _ if ch == '{' => *line_width += 1,
+            Some(last_newline) => *line_width = consumed.len() - last_newline,
+            None => *line_width += consumed.len(),
+        }
         i += 1;
     }
 
     Ok(())
 }
+
+/**
+Removes all source locations from the program.
+*/
+pub fn strip_locations(stack: &mut Vec<Stack>) {
+    stack.retain(|element| !matches!(element, Stack::Location(_)));
+
+    for element in stack.iter_mut() {
+        if let Stack::Value(ValueType::Scope(scope)) = element {
+            strip_locations(scope);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::ast;
+
+fn errors(src: &str) -> Vec<CheckError> {
+    let mut stack = Vec::new();
+    ast::fill(src, &mut stack, &mut 1, &mut 1, &mut HashMap::new()).unwrap();
+
+    check(&stack)
+        .into_iter()
+        .map(|diagnostic| diagnostic.error)
+        .collect()
+}
+
+#[test]
+fn test_check_examples() {
+    for path in [
+        "main.sl",
+        "examples/christmas_tree.sl",
+        "examples/fibonacci.sl",
+        "examples/fizzbuzz.sl",
+        "examples/range.sl",
+    ] {
+        let src = std::fs::read_to_string(path).unwrap();
+
+        assert_eq!(errors(&src), vec![], "{path}");
+    }
+}
+
+#[test]
+fn test_check_underflow() {
+    assert_eq!(
+        errors("1 +"),
+        vec![CheckError::Underflow {
+            operation: "+".to_string(),
+            needed: 2,
+            got: 1,
+        }]
+    );
+    assert_eq!(
+        errors("1 2 {+} 3 for"),
+        vec![CheckError::Underflow {
+            operation: "+".to_string(),
+            needed: 2,
+            got: 1,
+        }]
+    );
+    assert_eq!(
+        errors("1 2 3 {+} 100 for"),
+        vec![CheckError::Underflow {
+            operation: "for".to_string(),
+            needed: 101,
+            got: 3,
+        }]
+    );
+    assert_eq!(errors("pop 1 2 {+} 1 for"), vec![]);
+}
+
+#[test]
+fn test_check_mismatched_types() {
+    assert_eq!(
+        errors(r#""a" 1 +"#),
+        vec![CheckError::MismatchedTypes {
+            operation: "+".to_string(),
+            expected: "a numeric type".to_string(),
+            got: "Text".to_string(),
+        }]
+    );
+    assert_eq!(
+        errors("1 1.5 <"),
+        vec![CheckError::MismatchedTypes {
+            operation: "<".to_string(),
+            expected: "Float".to_string(),
+            got: "Int".to_string(),
+        }]
+    );
+    assert_eq!(
+        errors("{1} 2.0 for"),
+        vec![CheckError::MismatchedTypes {
+            operation: "for".to_string(),
+            expected: "Int".to_string(),
+            got: "Float".to_string(),
+        }]
+    );
+    assert_eq!(
+        errors(r#"1 {1.5 +} true if"#),
+        vec![CheckError::MismatchedTypes {
+            operation: "+".to_string(),
+            expected: "Int".to_string(),
+            got: "Float".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_check_loops() {
+    assert_eq!(
+        errors("1 {copy} true while"),
+        vec![CheckError::UnpredictableLoop {
+            operation: "while".to_string(),
+            change: 1,
+        }]
+    );
+    // The number of iterations is a constant.
+    assert_eq!(errors("1 {copy} 2 3 + copy * for"), vec![]);
+    assert_eq!(
+        errors("0 {1 +} 30 for {1} switch for"),
+        vec![CheckError::UnpredictableLoop {
+            operation: "for".to_string(),
+            change: 1,
+        }]
+    );
+    // The type left by the loop changes between iterations.
+    assert_eq!(
+        errors(r#"1 {pop "a"} 30 for 1 +"#),
+        vec![CheckError::MismatchedTypes {
+            operation: "+".to_string(),
+            expected: "a numeric type".to_string(),
+            got: "Text".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_check_locations() {
+    let mut stack = Vec::new();
+    ast::fill("1\n  +", &mut stack, &mut 1, &mut 1, &mut HashMap::new()).unwrap();
+
+    let diagnostics = check(&stack);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].location.as_ref().map(ToString::to_string),
+        Some("2:3".to_string())
+    );
+}
+
+#[test]
+fn test_check_macro_effects() {
+    assert_eq!(
+        errors("{copy *} \"square\" ( n -- n ) macro square"),
+        vec![CheckError::Underflow {
+            operation: "square".to_string(),
+            needed: 1,
+            got: 0,
+        }]
+    );
+
+    let effect = Effect {
+        name: "swap_sum".to_string(),
+        inputs: vec!["a".to_string(), "b".to_string()],
+        outputs: vec!["sum".to_string()],
+    };
+    let body = |src: &str| {
+        let mut stack = Vec::new();
+        ast::fill(src, &mut stack, &mut 1, &mut 1, &mut HashMap::new()).unwrap();
+        stack
+    };
+
+    assert_eq!(verify_effect(&body("switch +"), &effect), Ok(()));
+    assert_eq!(verify_effect(&body("{pop} true if"), &effect), Ok(()));
+    assert_eq!(
+        verify_effect(&body("+ +"), &effect),
+        Err(CheckError::Underflow {
+            operation: "+".to_string(),
+            needed: 2,
+            got: 1,
+        })
+    );
+    assert_eq!(
+        verify_effect(&body("pop pop"), &effect),
+        Err(CheckError::EffectMismatch {
+            effect: "( a b -- sum )".to_string(),
+            expected: 1,
+            got: 0,
+        })
+    );
+}
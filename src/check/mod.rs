@@ -0,0 +1,619 @@
+#[cfg(test)]
+mod check_test;
+
+use crate::{
+    util::error::CheckError, Effect, Location, OperationType, OperationType::*, Stack, ValueType,
+};
+
+/// Loops with a known number of iterations up to this one are checked iteration by iteration.
+const UNROLL_LIMIT: i64 = 16;
+/// Values a loop may leave before the checker stops keeping track of each one.
+const TRACKED_VALUES: i64 = 1 << 16;
+
+/// Problem found by `check` and where it is.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Location of the operation, if the program contains locations.
+    pub location: Option<Location>,
+    pub error: CheckError,
+}
+
+/**
+Finds mistakes in the program without running it.
+
+The program is interpreted abstractly: instead of values the checker keeps their types
+(and Ints that are constant), so it can tell how deep the stack is and what's on it for every operation.
+It reports:
+- operations that need more values than the stack can hold,
+- operations given values of the wrong type, like `"a" 1 +`,
+- loops changing the depth of the stack by a different amount on every run of the program.
+
+Branches and loops whose effect can't be known make the checker forget what is below them,
+so problems past that point may be missed.
+
+Example:
+```text
+1 +          // `+` needs 2 value(s) but the stack can hold only 1.
+```
+*/
+pub fn check(stack: &[Stack]) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+
+    checker.scope(stack, State::default());
+
+    checker.diagnostics
+}
+
+/**
+Verifies the body of a macro against its declared stack effect, as far as it can be known.
+
+The body starts with only the declared inputs on the stack, so it can't take more,
+and it has to leave as many values as declared unless that depends on the values.
+*/
+pub fn verify_effect(body: &[Stack], effect: &Effect) -> Result<(), CheckError> {
+    let mut checker = Checker::default();
+    let inputs = effect.inputs.len();
+
+    let state = checker.scope(
+        body,
+        State {
+            values: vec![Value::Unknown; inputs],
+            bottomless: false,
+            depth: Some(inputs as i64),
+            lowest: inputs as i64,
+        },
+    );
+
+    if let Some(underflow) = checker
+        .diagnostics
+        .into_iter()
+        .find(|diagnostic| matches!(diagnostic.error, CheckError::Underflow { .. }))
+    {
+        return Err(underflow.error);
+    }
+
+    match state.depth {
+        Some(depth) if depth != effect.outputs.len() as i64 => Err(CheckError::EffectMismatch {
+            effect: effect.to_string(),
+            expected: effect.outputs.len(),
+            got: depth.max(0) as usize,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// What is known about a value.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(Option<i64>),
+    Float,
+    Text,
+    Bool,
+    Scope(Option<Vec<Stack>>),
+    Unknown,
+}
+
+impl Value {
+    fn of(value: &ValueType) -> Value {
+        match value {
+            ValueType::Int(int) => Value::Int(Some(*int)),
+            ValueType::Float(_) => Value::Float,
+            ValueType::Text(_) => Value::Text,
+            ValueType::Bool(_) => Value::Bool,
+            ValueType::Scope(scope) => Value::Scope(Some(scope.clone())),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "Int",
+            Value::Float => "Float",
+            Value::Text => "Text",
+            Value::Bool => "Bool",
+            Value::Scope(_) => "Scope",
+            Value::Unknown => "a value",
+        }
+    }
+
+    /// What is known about a value that is either `self` or `other`.
+    fn merge(&self, other: &Value) -> Value {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Value::Int(if a == b { *a } else { None }),
+            (Value::Scope(a), Value::Scope(b)) => {
+                Value::Scope(if a == b { a.clone() } else { None })
+            }
+            (a, b) if a == b => a.clone(),
+            _ => Value::Unknown,
+        }
+    }
+}
+
+/// Stack at some point of the program.
+#[derive(Debug, Clone)]
+struct State {
+    /// Known values at the top of the stack.
+    values: Vec<Value>,
+    /// There may be values below the known ones, so running out of them isn't an error.
+    bottomless: bool,
+    /// Number of values on the stack, if it's the same on every run.
+    depth: Option<i64>,
+    /// Lowest depth reached since the start of the scope being checked.
+    lowest: i64,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            values: Vec::new(),
+            bottomless: false,
+            depth: Some(0),
+            lowest: 0,
+        }
+    }
+}
+
+impl State {
+    fn push(&mut self, value: Value) {
+        self.values.push(value);
+        self.depth = self.depth.map(|depth| depth + 1);
+    }
+
+    fn pop(&mut self) -> Value {
+        if let Some(depth) = &mut self.depth {
+            if *depth > 0 || self.bottomless {
+                *depth -= 1;
+                self.lowest = self.lowest.min(*depth);
+            }
+        }
+
+        self.values.pop().unwrap_or(Value::Unknown)
+    }
+
+    /// What is known about a stack that is either `self` or `other`.
+    fn merge(&self, other: &State) -> State {
+        let length = self.values.len().min(other.values.len());
+        let values = self.values[self.values.len() - length..]
+            .iter()
+            .zip(&other.values[other.values.len() - length..])
+            .map(|(a, b)| a.merge(b))
+            .collect();
+
+        State {
+            values,
+            bottomless: self.bottomless
+                || other.bottomless
+                || self.values.len() != other.values.len(),
+            depth: if self.depth == other.depth {
+                self.depth
+            } else {
+                None
+            },
+            lowest: self.lowest.min(other.lowest),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+    location: Option<Location>,
+}
+
+impl Checker {
+    fn report(&mut self, error: CheckError) {
+        let diagnostic = Diagnostic {
+            location: self.location.clone(),
+            error,
+        };
+
+        // Loop bodies are checked more than once.
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn scope(&mut self, stack: &[Stack], mut state: State) -> State {
+        for element in stack {
+            match element {
+                Stack::Location(location) => self.location = Some(location.clone()),
+                Stack::Value(value) => state.push(Value::of(value)),
+                Stack::Operation(operation) => self.operation(*operation, &mut state),
+                Stack::Effect(effect) => {
+                    self.require_values(&effect.name, effect.inputs.len(), &mut state)
+                }
+                Stack::EffectEnd(_) | Stack::LocalFrame | Stack::LocalFrameEnd => {}
+            }
+        }
+
+        state
+    }
+
+    /// Checks the scope as the body of a branch or a loop, starting from `state`.
+    fn body(&mut self, body: &[Stack], state: &State) -> State {
+        let location = self.location.clone();
+
+        let mut entry = state.clone();
+        entry.lowest = entry.depth.unwrap_or(0);
+        let exit = self.scope(body, entry);
+
+        self.location = location;
+
+        exit
+    }
+
+    /// Makes sure the stack holds `needed` values, reporting it once if it can't.
+    fn require(&mut self, operation: OperationType, needed: usize, state: &mut State) {
+        self.require_values(operation.keyword(), needed, state)
+    }
+
+    /// Same as `require` for an operation or a macro with the given name.
+    fn require_values(&mut self, operation: &str, needed: usize, state: &mut State) {
+        if !state.bottomless && state.values.len() < needed {
+            self.report(CheckError::Underflow {
+                operation: operation.to_string(),
+                needed,
+                got: state.values.len(),
+            });
+            // The program would stop here, there's no point in reporting the same values again.
+            state.bottomless = true;
+        }
+    }
+
+    fn expect(&mut self, operation: OperationType, expected: &str, got: &Value) {
+        if *got != Value::Unknown && got.name() != expected {
+            self.report(CheckError::MismatchedTypes {
+                operation: operation.keyword().to_string(),
+                expected: expected.to_string(),
+                got: got.name().to_string(),
+            });
+        }
+    }
+
+    /// Both values have to be the same type of number, `first` decides which one.
+    fn expect_numbers(&mut self, operation: OperationType, first: &Value, second: &Value) {
+        match first {
+            Value::Int(_) | Value::Float => self.expect(operation, first.name(), second),
+            Value::Unknown => {}
+            _ => self.expect(operation, "a numeric type", first),
+        }
+    }
+
+    fn operation(&mut self, operation: OperationType, state: &mut State) {
+        match operation {
+            Add | Sub | Mul | Div | Mod | Pow => {
+                self.require(operation, 2, state);
+                let last = state.pop();
+                let next_to_last = state.pop();
+                self.expect_numbers(operation, &next_to_last, &last);
+
+                state.push(match (&next_to_last, &last) {
+                    (Value::Int(a), Value::Int(b)) => Value::Int(fold(operation, *a, *b)),
+                    (Value::Float, _) => Value::Float,
+                    _ => Value::Unknown,
+                });
+            }
+            Lt | Gt | Eq | Leq | Geq => {
+                self.require(operation, 2, state);
+                let last = state.pop();
+                let next_to_last = state.pop();
+                self.expect_numbers(operation, &last, &next_to_last);
+
+                state.push(Value::Bool);
+            }
+            Or | And => {
+                self.require(operation, 2, state);
+                state.pop();
+                state.pop();
+                state.push(Value::Bool);
+            }
+            Not => {
+                self.require(operation, 1, state);
+                let value = match state.pop() {
+                    Value::Int(_) => Value::Int(None),
+                    Value::Scope(_) => Value::Scope(None),
+                    value => value,
+                };
+                state.push(value);
+            }
+            Print | Println | PrintDebug | PrintDebugStack | Copy => {
+                self.require(operation, 1, state);
+                if operation == Copy {
+                    let value = state.pop();
+                    state.push(value.clone());
+                    state.push(value);
+                }
+            }
+            Switch => {
+                self.require(operation, 2, state);
+                let last = state.pop();
+                let next_to_last = state.pop();
+                state.push(last);
+                state.push(next_to_last);
+            }
+            Reverse => {
+                if state.bottomless {
+                    // The values below the known ones come up.
+                    state.values.fill(Value::Unknown);
+                } else {
+                    state.values.reverse();
+                }
+            }
+            Pop => {
+                state.pop();
+            }
+            If => {
+                self.require(operation, 2, state);
+                state.pop();
+                let scope = state.pop();
+                self.expect(operation, "Scope", &scope);
+
+                if let Value::Scope(Some(body)) = scope {
+                    let taken = self.body(&body, state);
+                    *state = state.merge(&taken);
+                } else {
+                    forget(state);
+                }
+            }
+            Try => {
+                self.require(operation, 2, state);
+                let handler = state.pop();
+                self.expect(operation, "Scope", &handler);
+                let risky = state.pop();
+                self.expect(operation, "Scope", &risky);
+
+                if let (Value::Scope(Some(risky)), Value::Scope(Some(handler))) = (risky, handler) {
+                    let succeeded = self.body(&risky, state);
+                    let mut failed = state.clone();
+                    failed.push(Value::Text);
+                    failed.push(Value::Text);
+                    let handled = self.body(&handler, &failed);
+                    *state = succeeded.merge(&handled);
+                } else {
+                    forget(state);
+                }
+            }
+            Throw => {
+                self.require(operation, 2, state);
+                for _ in 0..2 {
+                    let text = state.pop();
+                    self.expect(operation, "Text", &text);
+                }
+            }
+            Assert => {
+                self.require(operation, 1, state);
+                state.pop();
+            }
+            AssertEq => {
+                self.require(operation, 2, state);
+                state.pop();
+                state.pop();
+            }
+            AssertStack => {
+                self.require(operation, 1, state);
+                let expected = state.pop();
+                self.expect(operation, "Scope", &expected);
+            }
+            Test => {
+                self.require(operation, 2, state);
+                let body = state.pop();
+                self.expect(operation, "Scope", &body);
+                let name = state.pop();
+                self.expect(operation, "Text", &name);
+
+                // Tests run later, each on an empty stack.
+                if let Value::Scope(Some(body)) = body {
+                    self.body(&body, &State::default());
+                }
+            }
+            For => self.for_loop(state),
+            While => self.while_loop(state),
+            FileRead | FileExists | DirList => {
+                self.require(operation, 1, state);
+                let path = state.pop();
+                self.expect(operation, "Text", &path);
+
+                state.push(match operation {
+                    FileRead => Value::Text,
+                    FileExists => Value::Bool,
+                    _ => Value::Scope(None),
+                });
+            }
+            FileWrite | FileAppend => {
+                self.require(operation, 2, state);
+                let path = state.pop();
+                self.expect(operation, "Text", &path);
+                let content = state.pop();
+                self.expect(operation, "Text", &content);
+            }
+            LocalSet | Set => {
+                self.require(operation, 2, state);
+                let name = state.pop();
+                self.expect(operation, "Text", &name);
+                state.pop();
+            }
+            LocalGet | Get => {
+                self.require(operation, 1, state);
+                let name = state.pop();
+                self.expect(operation, "Text", &name);
+                state.push(Value::Unknown);
+            }
+            PrintDebugVars | Macro | Const | Import | ImportAs => {}
+        }
+    }
+
+    fn for_loop(&mut self, state: &mut State) {
+        self.require(For, 2, state);
+        let count = state.pop();
+        self.expect(For, "Int", &count);
+        let scope = state.pop();
+        self.expect(For, "Scope", &scope);
+
+        let Value::Scope(Some(body)) = scope else {
+            return forget(state);
+        };
+
+        let count = match count {
+            Value::Int(count) => count,
+            Value::Unknown => None,
+            // The loop fails at runtime.
+            _ => return forget(state),
+        };
+        let once = self.body(&body, state);
+
+        match (state.depth, once.depth, count) {
+            (_, _, Some(count)) if count <= 0 => {}
+            (_, _, Some(count)) if count <= UNROLL_LIMIT => {
+                let mut lowest = state.lowest.min(once.lowest);
+                *state = once;
+                for _ in 1..count {
+                    *state = self.body(&body, state);
+                    lowest = lowest.min(state.lowest);
+                }
+                state.lowest = lowest;
+            }
+            (Some(before), Some(after), _) if before == after => {
+                *state = self.stable_loop(&body, state, &once, count.is_some())
+            }
+            (Some(before), Some(after), Some(count)) => {
+                *state = self.repeat(state, &once, before, after - before, count)
+            }
+            (Some(before), Some(after), None) => {
+                self.report(CheckError::UnpredictableLoop {
+                    operation: For.keyword().to_string(),
+                    change: after - before,
+                });
+                *state = state.merge(&once);
+                state.depth = None;
+            }
+            // The body itself was unpredictable.
+            _ => *state = state.merge(&once),
+        }
+    }
+
+    fn while_loop(&mut self, state: &mut State) {
+        self.require(While, 2, state);
+        let condition = state.pop();
+        let scope = state.pop();
+        self.expect(While, "Scope", &scope);
+        state.push(condition);
+
+        let Value::Scope(Some(body)) = scope else {
+            return forget(state);
+        };
+
+        let once = self.body(&body, state);
+        match (state.depth, once.depth) {
+            (Some(before), Some(after)) if before == after => {
+                *state = self.stable_loop(&body, state, &once, false)
+            }
+            (Some(before), Some(after)) => {
+                self.report(CheckError::UnpredictableLoop {
+                    operation: While.keyword().to_string(),
+                    change: after - before,
+                });
+                *state = state.merge(&once);
+                state.depth = None;
+            }
+            _ => *state = state.merge(&once),
+        }
+    }
+
+    /**
+    State after a loop whose body keeps the depth, checking the body once more with what it could leave.
+
+    `runs` tells that the body runs at least once, so the state before the loop can't be left as it was.
+    */
+    fn stable_loop(&mut self, body: &[Stack], before: &State, once: &State, runs: bool) -> State {
+        let merged = before.merge(once);
+        let twice = self.body(body, &merged);
+
+        let mut after = if runs {
+            once.merge(&twice)
+        } else {
+            merged.merge(&twice)
+        };
+        after.lowest = after.lowest.min(before.lowest);
+
+        after
+    }
+
+    /// State after `count` iterations of a body changing the depth by `change`, `once` is the state after the first one.
+    fn repeat(
+        &mut self,
+        before: &State,
+        once: &State,
+        depth: i64,
+        change: i64,
+        count: i64,
+    ) -> State {
+        let taken = depth - once.lowest;
+        // Values below this depth aren't touched by any iteration.
+        let untouched = if change > 0 {
+            depth - taken
+        } else {
+            (count - 1)
+                .checked_mul(change)
+                .map_or(i64::MIN, |change| depth + change - taken)
+        };
+        let after = count
+            .checked_mul(change)
+            .and_then(|change| depth.checked_add(change));
+
+        if untouched < 0 && !before.bottomless {
+            self.report(CheckError::Underflow {
+                operation: For.keyword().to_string(),
+                needed: (depth - untouched) as usize,
+                got: depth as usize,
+            });
+
+            return State {
+                values: Vec::new(),
+                bottomless: true,
+                depth: None,
+                lowest: before.lowest,
+            };
+        }
+
+        let known_from = depth - before.values.len() as i64;
+        let kept = (untouched - known_from).clamp(0, before.values.len() as i64) as usize;
+        let mut state = State {
+            values: before.values[..kept].to_vec(),
+            bottomless: before.bottomless,
+            depth: after,
+            lowest: before.lowest.min(untouched),
+        };
+
+        match after.map(|after| after - untouched.max(known_from)) {
+            Some(added) if added <= TRACKED_VALUES => {
+                state.values.extend((0..added).map(|_| Value::Unknown))
+            }
+            _ => {
+                state.values.clear();
+                state.bottomless = true;
+            }
+        }
+
+        state
+    }
+}
+
+/// After a scope that isn't known nothing is known about the stack.
+fn forget(state: &mut State) {
+    state.values.clear();
+    state.bottomless = true;
+    state.depth = None;
+}
+
+/// Result of a math operation on constant Ints, `None` when it isn't constant or would fail.
+fn fold(operation: OperationType, a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    let (a, b) = (a?, b?);
+
+    match operation {
+        Add => a.checked_add(b),
+        Sub => a.checked_sub(b),
+        Mul => a.checked_mul(b),
+        Div => a.checked_div(b),
+        Mod => a.checked_rem(b),
+        _ => None,
+    }
+}
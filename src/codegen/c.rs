@@ -0,0 +1,199 @@
+use std::fmt::Write;
+
+use super::Printed;
+use crate::{
+    util::error::{self, CodegenError},
+    OperationType, Stack, ValueType,
+};
+
+const RUNTIME: &str = include_str!("c_runtime.c");
+
+/**
+Transpiles the program to a standalone C99 file.
+
+The output only depends on the C standard library:
+```sh
+cc program.c -o program -lm
+```
+
+Every scope is compiled into a function and values are kept on a value stack of tagged unions, see `c_runtime.c`.
+File operations are not supported.
+*/
+pub fn compile(stack: &[Stack]) -> error::codegen::Result<String> {
+    let mut generator = Generator::default();
+
+    generator.scope("program".to_string(), stack)?;
+
+    // Results of `!` on Texts and Scopes.
+    generator.descriptor("text_empty", None, &ValueType::Text(String::new()));
+    generator.descriptor("text_true", None, &ValueType::Text("true".to_string()));
+    generator.scope("scope_empty_code".to_string(), &[])?;
+    generator.descriptor(
+        "scope_empty",
+        Some("scope_empty_code"),
+        &ValueType::Scope(vec![]),
+    );
+    let scope_true = [Stack::Value(ValueType::Bool(true))];
+    generator.scope("scope_true_code".to_string(), &scope_true)?;
+    generator.descriptor(
+        "scope_true",
+        Some("scope_true_code"),
+        &ValueType::Scope(scope_true.to_vec()),
+    );
+
+    Ok(format!(
+        "{RUNTIME}\n/* ------------------------------------------------------------------------- */\n/* Program                                                                   */\n/* ------------------------------------------------------------------------- */\n\n{}\n{}\n{}int main(void) {{\n    program();\n    return 0;\n}}\n",
+        generator.prototypes, generator.descriptors, generator.functions
+    ))
+}
+
+#[derive(Default)]
+struct Generator {
+    prototypes: String,
+    descriptors: String,
+    functions: String,
+    labels: usize,
+}
+
+impl Generator {
+    fn label(&mut self, prefix: &str) -> String {
+        self.labels += 1;
+
+        format!("{prefix}_{}", self.labels)
+    }
+
+    /// Compiles the scope into a function with the given name.
+    fn scope(&mut self, label: String, stack: &[Stack]) -> error::codegen::Result<()> {
+        let mut body = format!("static void {label}(void) {{\n");
+
+        for element in stack {
+            match element {
+                Stack::Location(location) => writeln!(body, "    // {location}").unwrap(),
+                // Declared stack effects are only checked by the interpreter.
+                Stack::Effect(effect) => {
+                    writeln!(body, "    // macro {} {effect}", effect.name).unwrap()
+                }
+                Stack::EffectEnd(effect) => {
+                    writeln!(body, "    // end of macro {}", effect.name).unwrap()
+                }
+                // Locals aren't supported by this target.
+                Stack::LocalFrame | Stack::LocalFrameEnd => (),
+                Stack::Operation(operation) => {
+                    writeln!(body, "    {}();", routine(*operation)?).unwrap()
+                }
+                Stack::Value(ValueType::Int(int)) => {
+                    if *int == i64::MIN {
+                        writeln!(body, "    rt_push_int(INT64_MIN);").unwrap()
+                    } else {
+                        writeln!(body, "    rt_push_int(INT64_C({int}));").unwrap()
+                    }
+                }
+                Stack::Value(ValueType::Float(float)) => {
+                    writeln!(body, "    rt_push_float({});", float_literal(*float)).unwrap()
+                }
+                Stack::Value(ValueType::Bool(condition)) => {
+                    writeln!(body, "    rt_push_bool({condition});").unwrap()
+                }
+                Stack::Value(value @ ValueType::Text(_)) => {
+                    let descriptor = self.label("text");
+                    self.descriptor(&descriptor, None, value);
+
+                    writeln!(body, "    rt_push_descriptor(TAG_TEXT, &{descriptor});").unwrap()
+                }
+                Stack::Value(value @ ValueType::Scope(scope)) => {
+                    let code = self.label("scope");
+                    self.scope(code.clone(), scope)?;
+                    let descriptor = format!("{code}_descriptor");
+                    self.descriptor(&descriptor, Some(&code), value);
+
+                    writeln!(body, "    rt_push_descriptor(TAG_SCOPE, &{descriptor});").unwrap()
+                }
+            }
+        }
+
+        body.push_str("}\n\n");
+        self.functions.push_str(&body);
+        writeln!(self.prototypes, "static void {label}(void);").unwrap();
+
+        Ok(())
+    }
+
+    /// Describes a Text or a Scope with all the ways it can be printed.
+    fn descriptor(&mut self, label: &str, code: Option<&str>, value: &ValueType) {
+        let printed = Printed::new(value);
+
+        writeln!(
+            self.descriptors,
+            "static const Descriptor {label} = {{\n    {},\n    {},\n    {},\n    {},\n    {},\n}};",
+            code.unwrap_or("NULL"),
+            printed.count,
+            string(&printed.display),
+            string(&printed.debug),
+            string(&printed.entry),
+        )
+        .unwrap();
+    }
+}
+
+/// Gives a `Str` initializer, non printable characters are escaped.
+fn string(content: &str) -> String {
+    let mut escaped = String::with_capacity(content.len());
+    for byte in content.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => write!(escaped, "\\{}", byte as char).unwrap(),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => write!(escaped, "\\{byte:03o}").unwrap(),
+        }
+    }
+
+    format!("{{\"{escaped}\", {}}}", content.len())
+}
+
+/// Exponent notation is the shortest representation that reads back as the same number.
+fn float_literal(float: f64) -> String {
+    if float.is_nan() {
+        "NAN".to_string()
+    } else if float.is_infinite() {
+        format!("{}INFINITY", if float < 0.0 { "-" } else { "" })
+    } else {
+        format!("{float:e}")
+    }
+}
+
+fn routine(operation: OperationType) -> error::codegen::Result<&'static str> {
+    use OperationType::*;
+
+    Ok(match operation {
+        Add => "op_add",
+        Sub => "op_sub",
+        Mul => "op_mul",
+        Div => "op_div",
+        Mod => "op_mod",
+        Pow => "op_pow",
+        Print => "op_print",
+        PrintDebug => "op_print_debug",
+        PrintDebugStack => "op_print_debug_stack",
+        If => "op_if",
+        Lt => "op_lt",
+        Gt => "op_gt",
+        Eq => "op_eq",
+        Leq => "op_leq",
+        Geq => "op_geq",
+        Or => "op_or",
+        And => "op_and",
+        For => "op_for",
+        While => "op_while",
+        Switch => "op_switch",
+        Reverse => "op_reverse",
+        Pop => "op_pop",
+        Not => "op_not",
+        Copy => "op_copy",
+        Println => "op_println",
+        _ => {
+            return Err(CodegenError::Unsupported {
+                target: "c".to_string(),
+                what: format!("`{}`", operation.keyword()),
+            })
+        }
+    })
+}
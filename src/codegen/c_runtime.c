@@ -0,0 +1,529 @@
+/*
+ * Runtime of programs compiled with `build --target c`.
+ *
+ * Values live on a value stack of tagged unions. Ints, Floats and Bools are stored
+ * directly, Texts and Scopes point to a descriptor generated by the compiler.
+ */
+
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define VALUE_STACK_CAPACITY 1048576
+
+typedef struct {
+    const char *ptr;
+    size_t len;
+} Str;
+
+typedef struct {
+    /* Code of the Scope, NULL for Texts. */
+    void (*code)(void);
+    /* Elements of the Scope or bytes of the Text. */
+    size_t count;
+    Str display;
+    Str debug;
+    /* The way the value appears in the pretty printed value stack. */
+    Str entry;
+} Descriptor;
+
+typedef enum { TAG_INT, TAG_FLOAT, TAG_TEXT, TAG_SCOPE, TAG_BOOL } Tag;
+
+typedef struct {
+    Tag tag;
+    union {
+        int64_t int_value;
+        double float_value;
+        bool bool_value;
+        const Descriptor *descriptor;
+    } as;
+} Value;
+
+static Value value_stack[VALUE_STACK_CAPACITY];
+static size_t value_stack_len = 0;
+
+/* Results of `!` on Texts and Scopes. */
+static const Descriptor text_empty;
+static const Descriptor text_true;
+static const Descriptor scope_empty;
+static const Descriptor scope_true;
+
+/* ------------------------------------------------------------------------- */
+/* Output                                                                    */
+/* ------------------------------------------------------------------------- */
+
+static void rt_write(const char *ptr, size_t len) {
+    fwrite(ptr, 1, len, stdout);
+}
+
+static void rt_write_str(Str str) {
+    rt_write(str.ptr, str.len);
+}
+
+static void rt_write_cstr(const char *str) {
+    rt_write(str, strlen(str));
+}
+
+static void rt_write_int(int64_t value) {
+    char buffer[32];
+    int len = snprintf(buffer, sizeof buffer, "%lld", (long long)value);
+    rt_write(buffer, (size_t)len);
+}
+
+/*
+ * Formats the Float the same way as Rust's `Display`, or `Debug` if `debug` is set.
+ *
+ * Both use the shortest representation that reads back as the same number. `Display`
+ * never uses an exponent, `Debug` uses one for very small and very large numbers and
+ * always has a fractional part otherwise.
+ */
+static void rt_write_float(double value, bool debug) {
+    if (isnan(value)) {
+        rt_write_cstr("NaN");
+        return;
+    }
+
+    char buffer[400];
+    size_t len = 0;
+
+    if (signbit(value)) {
+        buffer[len++] = '-';
+        value = -value;
+    }
+
+    if (isinf(value)) {
+        rt_write(buffer, len);
+        rt_write_cstr("inf");
+        return;
+    }
+
+    if (value == 0.0) {
+        rt_write(buffer, len);
+        rt_write_cstr(debug ? "0.0" : "0");
+        return;
+    }
+
+    /* Shortest "d.ddde[+-]x" that reads back as the same number. */
+    char scientific[32];
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(scientific, sizeof scientific, "%.*e", precision, value);
+        if (strtod(scientific, NULL) == value) {
+            break;
+        }
+    }
+
+    char digits[20];
+    size_t digit_count = 0;
+    char *cursor = scientific;
+    for (; *cursor != 'e'; cursor++) {
+        if (*cursor != '.') {
+            digits[digit_count++] = *cursor;
+        }
+    }
+    int exponent = atoi(cursor + 1);
+    while (digit_count > 1 && digits[digit_count - 1] == '0') {
+        digit_count--;
+    }
+
+    if (debug && (value < 1e-4 || value >= 1e16)) {
+        buffer[len++] = digits[0];
+        if (digit_count > 1) {
+            buffer[len++] = '.';
+            memcpy(buffer + len, digits + 1, digit_count - 1);
+            len += digit_count - 1;
+        }
+        len += (size_t)snprintf(buffer + len, sizeof buffer - len, "e%d", exponent);
+        rt_write(buffer, len);
+        return;
+    }
+
+    if (exponent < 0) {
+        buffer[len++] = '0';
+        buffer[len++] = '.';
+        for (int i = -1; i > exponent; i--) {
+            buffer[len++] = '0';
+        }
+        memcpy(buffer + len, digits, digit_count);
+        len += digit_count;
+    } else {
+        size_t integer_digits = (size_t)exponent + 1;
+        for (size_t i = 0; i < integer_digits; i++) {
+            buffer[len++] = i < digit_count ? digits[i] : '0';
+        }
+        if (digit_count > integer_digits) {
+            buffer[len++] = '.';
+            memcpy(buffer + len, digits + integer_digits, digit_count - integer_digits);
+            len += digit_count - integer_digits;
+        } else if (debug) {
+            buffer[len++] = '.';
+            buffer[len++] = '0';
+        }
+    }
+
+    rt_write(buffer, len);
+}
+
+static void rt_write_display(Value value) {
+    switch (value.tag) {
+    case TAG_INT:
+        rt_write_int(value.as.int_value);
+        break;
+    case TAG_FLOAT:
+        rt_write_float(value.as.float_value, false);
+        break;
+    case TAG_BOOL:
+        rt_write_cstr(value.as.bool_value ? "true" : "false");
+        break;
+    case TAG_TEXT:
+    case TAG_SCOPE:
+        rt_write_str(value.as.descriptor->display);
+        break;
+    }
+}
+
+static void rt_write_debug(Value value) {
+    switch (value.tag) {
+    case TAG_INT:
+        rt_write_cstr("Int(");
+        rt_write_int(value.as.int_value);
+        rt_write_cstr(")");
+        break;
+    case TAG_FLOAT:
+        rt_write_cstr("Float(");
+        rt_write_float(value.as.float_value, true);
+        rt_write_cstr(")");
+        break;
+    case TAG_BOOL:
+        rt_write_cstr("Bool(");
+        rt_write_display(value);
+        rt_write_cstr(")");
+        break;
+    case TAG_TEXT:
+    case TAG_SCOPE:
+        rt_write_str(value.as.descriptor->debug);
+        break;
+    }
+}
+
+static void rt_write_entry(Value value) {
+    switch (value.tag) {
+    case TAG_INT:
+        rt_write_cstr("    Int(\n        ");
+        rt_write_int(value.as.int_value);
+        rt_write_cstr(",\n    ),\n");
+        break;
+    case TAG_FLOAT:
+        rt_write_cstr("    Float(\n        ");
+        rt_write_float(value.as.float_value, true);
+        rt_write_cstr(",\n    ),\n");
+        break;
+    case TAG_BOOL:
+        rt_write_cstr("    Bool(\n        ");
+        rt_write_display(value);
+        rt_write_cstr(",\n    ),\n");
+        break;
+    case TAG_TEXT:
+    case TAG_SCOPE:
+        rt_write_str(value.as.descriptor->entry);
+        break;
+    }
+}
+
+/* ------------------------------------------------------------------------- */
+/* Errors                                                                    */
+/* ------------------------------------------------------------------------- */
+
+static void rt_fail(const char *message) {
+    rt_write_cstr("\033[91mRuntime Error\033[0m\n");
+    rt_write_cstr(message);
+    fflush(stdout);
+    exit(1);
+}
+
+/* ------------------------------------------------------------------------- */
+/* Value stack                                                               */
+/* ------------------------------------------------------------------------- */
+
+static void rt_need(size_t amount) {
+    if (value_stack_len < amount) {
+        rt_fail("\tNot enough arguments on the stack.\n");
+    }
+}
+
+static void rt_push(Value value) {
+    if (value_stack_len == VALUE_STACK_CAPACITY) {
+        rt_fail("\tValue stack overflow.\n");
+    }
+    value_stack[value_stack_len++] = value;
+}
+
+static Value rt_pop(void) {
+    return value_stack[--value_stack_len];
+}
+
+static void rt_push_int(int64_t int_value) {
+    rt_push((Value){.tag = TAG_INT, .as.int_value = int_value});
+}
+
+static void rt_push_float(double float_value) {
+    rt_push((Value){.tag = TAG_FLOAT, .as.float_value = float_value});
+}
+
+static void rt_push_bool(bool bool_value) {
+    rt_push((Value){.tag = TAG_BOOL, .as.bool_value = bool_value});
+}
+
+static void rt_push_descriptor(Tag tag, const Descriptor *descriptor) {
+    rt_push((Value){.tag = tag, .as.descriptor = descriptor});
+}
+
+static bool rt_truthy(Value value) {
+    switch (value.tag) {
+    case TAG_INT:
+        return value.as.int_value != 0;
+    case TAG_FLOAT:
+        return value.as.float_value != 0.0;
+    case TAG_BOOL:
+        return value.as.bool_value;
+    case TAG_TEXT:
+    case TAG_SCOPE:
+        return value.as.descriptor->count != 0;
+    }
+    return false;
+}
+
+/* Pops two numbers of the same type, `a` is the next to last value and `b` the last one. */
+static Tag rt_pop_numbers(Value *a, Value *b) {
+    rt_need(2);
+    *b = rt_pop();
+    *a = rt_pop();
+    if (a->tag != b->tag || (a->tag != TAG_INT && a->tag != TAG_FLOAT)) {
+        rt_fail("\tMismatched types.\n");
+    }
+    return a->tag;
+}
+
+/* ------------------------------------------------------------------------- */
+/* Operations                                                                */
+/* ------------------------------------------------------------------------- */
+
+/* Ints wrap around on overflow. */
+#define MATH(name, operator)                                                   \
+    void name(void) {                                                          \
+        Value a, b;                                                            \
+        if (rt_pop_numbers(&a, &b) == TAG_INT) {                               \
+            rt_push_int((int64_t)((uint64_t)a.as.int_value                     \
+                                  operator(uint64_t) b.as.int_value));         \
+        } else {                                                               \
+            rt_push_float(a.as.float_value operator b.as.float_value);         \
+        }                                                                      \
+    }
+
+MATH(op_add, +)
+MATH(op_sub, -)
+MATH(op_mul, *)
+
+void op_div(void) {
+    Value a, b;
+    if (rt_pop_numbers(&a, &b) == TAG_FLOAT) {
+        rt_push_float(a.as.float_value / b.as.float_value);
+    } else if (b.as.int_value == 0) {
+        rt_fail("\tAttempt to divide by zero.\n");
+    } else if (b.as.int_value == -1) {
+        rt_push_int((int64_t)(0 - (uint64_t)a.as.int_value));
+    } else {
+        rt_push_int(a.as.int_value / b.as.int_value);
+    }
+}
+
+void op_mod(void) {
+    Value a, b;
+    if (rt_pop_numbers(&a, &b) == TAG_FLOAT) {
+        rt_push_float(fmod(a.as.float_value, b.as.float_value));
+    } else if (b.as.int_value == 0) {
+        rt_fail("\tAttempt to divide by zero.\n");
+    } else if (b.as.int_value == -1) {
+        rt_push_int(0);
+    } else {
+        rt_push_int(a.as.int_value % b.as.int_value);
+    }
+}
+
+void op_pow(void) {
+    Value a, b;
+    if (rt_pop_numbers(&a, &b) == TAG_FLOAT) {
+        rt_push_float(pow(a.as.float_value, b.as.float_value));
+        return;
+    }
+
+    /* The exponent is truncated to u32 like in the interpreter. */
+    uint32_t exponent = (uint32_t)b.as.int_value;
+    uint64_t base = (uint64_t)a.as.int_value;
+    uint64_t result = 1;
+    while (exponent != 0) {
+        if (exponent & 1) {
+            result *= base;
+        }
+        base *= base;
+        exponent >>= 1;
+    }
+    rt_push_int((int64_t)result);
+}
+
+/* Comparisons compare the last value with the next to last one. */
+#define COMPARISON(name, operator)                                             \
+    void name(void) {                                                          \
+        Value a, b;                                                            \
+        if (rt_pop_numbers(&a, &b) == TAG_INT) {                               \
+            rt_push_bool(b.as.int_value operator a.as.int_value);              \
+        } else {                                                               \
+            rt_push_bool(b.as.float_value operator a.as.float_value);          \
+        }                                                                      \
+    }
+
+COMPARISON(op_lt, <)
+COMPARISON(op_gt, >)
+COMPARISON(op_eq, ==)
+COMPARISON(op_leq, <=)
+COMPARISON(op_geq, >=)
+
+void op_or(void) {
+    rt_need(2);
+    bool last = rt_truthy(rt_pop());
+    bool next_to_last = rt_truthy(rt_pop());
+    rt_push_bool(last || next_to_last);
+}
+
+void op_and(void) {
+    rt_need(2);
+    bool last = rt_truthy(rt_pop());
+    bool next_to_last = rt_truthy(rt_pop());
+    rt_push_bool(last && next_to_last);
+}
+
+void op_not(void) {
+    rt_need(1);
+    Value value = rt_pop();
+    bool truthy = rt_truthy(value);
+
+    switch (value.tag) {
+    case TAG_INT:
+        rt_push_int(!truthy);
+        break;
+    case TAG_FLOAT:
+        rt_push_float(truthy ? 0.0 : 1.0);
+        break;
+    case TAG_BOOL:
+        rt_push_bool(!truthy);
+        break;
+    case TAG_TEXT:
+        rt_push_descriptor(TAG_TEXT, truthy ? &text_empty : &text_true);
+        break;
+    case TAG_SCOPE:
+        rt_push_descriptor(TAG_SCOPE, truthy ? &scope_empty : &scope_true);
+        break;
+    }
+}
+
+void op_if(void) {
+    rt_need(2);
+    Value condition = rt_pop();
+    Value scope = rt_pop();
+
+    if (rt_truthy(condition)) {
+        if (scope.tag != TAG_SCOPE) {
+            rt_fail("\tMismatched types.\n");
+        }
+        scope.as.descriptor->code();
+    }
+}
+
+void op_for(void) {
+    rt_need(2);
+    Value range = rt_pop();
+    Value scope = rt_pop();
+
+    if (range.tag != TAG_INT) {
+        rt_fail("\tMismatched types.\n");
+    }
+    if (scope.tag != TAG_SCOPE) {
+        return;
+    }
+    for (int64_t i = 0; i < range.as.int_value; i++) {
+        scope.as.descriptor->code();
+    }
+}
+
+void op_while(void) {
+    rt_need(2);
+    Value condition = rt_pop();
+    Value scope = rt_pop();
+    rt_push(condition);
+
+    if (scope.tag != TAG_SCOPE) {
+        return;
+    }
+    for (;;) {
+        rt_need(1);
+        if (!rt_truthy(value_stack[value_stack_len - 1])) {
+            break;
+        }
+        scope.as.descriptor->code();
+    }
+}
+
+void op_print(void) {
+    rt_need(1);
+    rt_write_display(value_stack[value_stack_len - 1]);
+}
+
+void op_println(void) {
+    op_print();
+    rt_write_cstr("\n");
+}
+
+void op_print_debug(void) {
+    rt_need(1);
+    rt_write_debug(value_stack[value_stack_len - 1]);
+    rt_write_cstr(" is ");
+    rt_write_int((int64_t)value_stack_len);
+    rt_write_cstr(" element in the stack\n");
+}
+
+void op_print_debug_stack(void) {
+    rt_need(1);
+    rt_write_cstr("[\n");
+    for (size_t i = 0; i < value_stack_len; i++) {
+        rt_write_entry(value_stack[i]);
+    }
+    rt_write_cstr("]\n");
+}
+
+void op_switch(void) {
+    rt_need(2);
+    Value last = value_stack[value_stack_len - 1];
+    value_stack[value_stack_len - 1] = value_stack[value_stack_len - 2];
+    value_stack[value_stack_len - 2] = last;
+}
+
+void op_reverse(void) {
+    for (size_t i = 0; i < value_stack_len / 2; i++) {
+        Value value = value_stack[i];
+        value_stack[i] = value_stack[value_stack_len - 1 - i];
+        value_stack[value_stack_len - 1 - i] = value;
+    }
+}
+
+void op_pop(void) {
+    if (value_stack_len != 0) {
+        value_stack_len--;
+    }
+}
+
+void op_copy(void) {
+    rt_need(1);
+    rt_push(value_stack[value_stack_len - 1]);
+}
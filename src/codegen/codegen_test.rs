@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::{ast, util::error::CodegenError, Stack};
+
+fn parse(src: &str) -> Vec<Stack> {
+    let mut stack = Vec::new();
+
+    ast::fill(src, &mut stack, &mut 1, &mut 1, &mut HashMap::new()).unwrap();
+
+    stack
+}
+
+#[test]
+fn test_x86_64_compile() {
+    let assembly = x86_64::compile(&parse("2 {3 *} true if println")).unwrap();
+
+    assert!(assembly.contains(
+        "program:\n    mov rdi, 2\n    mov rax, rdi\n    mov ecx, TAG_INT\n    call rt_push\n"
+    ));
+    assert!(assembly.contains("scope_1:\n    mov rdi, 3\n    # 1:6\n    mov rax, rdi\n    mov ecx, TAG_INT\n    call rt_push\n    call op_mul\n"));
+    assert!(assembly.contains("    call op_if\n"));
+    assert!(assembly.contains("    call op_println\n    ret\n"));
+}
+
+#[test]
+fn test_x86_64_caches_ints() {
+    let assembly = x86_64::compile(&parse("2 3 + copy * 4 switch - 7 8 pop println")).unwrap();
+
+    assert!(assembly.contains(
+        "program:\n    mov rdi, 2\n    mov rsi, 3\n    # 1:5\n    add rdi, rsi\n    # 1:7\n    mov rsi, rdi\n    # 1:12\n    imul rdi, rsi\n    mov rsi, 4\n    # 1:16\n    # 1:23\n    sub rsi, rdi\n    mov rdi, 7\n    mov r8, 8\n    # 1:29\n    # 1:33\n    lea rax, [r12 + 32]\n"
+    ));
+    // Both cached Ints are stored before the routine runs.
+    assert!(assembly.contains("    mov qword ptr [r12 + 0], TAG_INT\n    mov [r12 + 8], rsi\n    mov qword ptr [r12 + 16], TAG_INT\n    mov [r12 + 24], rdi\n    add r12, 32\n    call op_println\n"));
+}
+
+#[test]
+fn test_x86_64_escapes_strings() {
+    let assembly = x86_64::compile(&parse("\"tab\tand\nnewline\" println")).unwrap();
+
+    assert!(assembly.contains(".ascii \"tab\\011and\\012newline\""));
+}
+
+#[test]
+fn test_x86_64_unsupported() {
+    assert_eq!(
+        x86_64::compile(&parse("1.5 println")),
+        Err(CodegenError::Unsupported {
+            target: "x86_64-asm".to_string(),
+            what: "Float".to_string(),
+        })
+    );
+
+    assert!(x86_64::compile(&parse("\"a\" file_read")).is_err());
+}
+
+#[test]
+fn test_c_compile() {
+    let source = c::compile(&parse("2 {3.5 *} true if println")).unwrap();
+
+    assert!(source.contains("static void program(void) {\n    rt_push_int(INT64_C(2));\n"));
+    assert!(source.contains(
+        "static void scope_1(void) {\n    rt_push_float(3.5e0);\n    // 1:8\n    op_mul();\n}\n"
+    ));
+    assert!(
+        source.contains("static const Descriptor scope_1_descriptor = {\n    scope_1,\n    3,\n")
+    );
+    assert!(source.contains("    op_if();\n"));
+}
+
+#[test]
+fn test_c_literals() {
+    let source = c::compile(&parse(
+        "-9223372036854775808 \"tab\tand\nnewline? \\\" 0.0 0.0 /",
+    ))
+    .unwrap();
+
+    assert!(source.contains("    rt_push_int(INT64_MIN);\n"));
+    assert!(source.contains("{\"tab\\011and\\012newline\\? \\\\\", 18}"));
+    assert!(source.contains("    rt_push_float(0e0);\n"));
+}
+
+#[test]
+fn test_c_unsupported() {
+    assert_eq!(
+        c::compile(&parse("\"a\" file_read")),
+        Err(CodegenError::Unsupported {
+            target: "c".to_string(),
+            what: "`file_read`".to_string(),
+        })
+    );
+}
+
+#[test]
+fn test_wasm_compile() {
+    let module = wasm::compile(&parse("2 {3.5 *} true if println")).unwrap();
+
+    assert!(module.contains("  (func $program\n    (call $push_int (i64.const 2))\n"));
+    assert!(module.contains("  (func $scope_1\n    (call $push_float (f64.const 3.5e0))\n    ;; 1:8\n    (call $op_mul)\n  )\n"));
+    assert!(module.contains(
+        "  (elem (i32.const 0) func $scope_1 $program $scope_empty_code $scope_true_code)\n"
+    ));
+    assert!(module.contains("  (export \"run\" (func $program)))\n"));
+
+    let binary = wasm::compile_binary(&parse("2 {3.5 *} true if println")).unwrap();
+    assert_eq!(&binary[..4], b"\0asm");
+}
+
+#[test]
+fn test_wasm_unsupported() {
+    assert_eq!(
+        wasm::compile(&parse("\"a\" file_exists")),
+        Err(CodegenError::Unsupported {
+            target: "wasm".to_string(),
+            what: "`file_exists`".to_string(),
+        })
+    );
+}
@@ -0,0 +1,41 @@
+pub mod c;
+#[cfg(test)]
+mod codegen_test;
+pub mod wasm;
+pub mod x86_64;
+
+use crate::ValueType;
+
+/**
+Everything the generated code needs to know about a Text or a Scope.
+
+Strings are formatted by the interpreter's own `Display` and `Debug`, so compiled programs print the same.
+*/
+struct Printed {
+    /// Elements of the Scope or bytes of the Text, decides whether it's truthy.
+    count: usize,
+    display: String,
+    debug: String,
+    /// The way the value appears in the pretty printed value stack.
+    entry: String,
+}
+
+impl Printed {
+    fn new(value: &ValueType) -> Self {
+        let count = match value {
+            ValueType::Scope(scope) => scope.len(),
+            ValueType::Text(text) => text.len(),
+            _ => unreachable!("Only Texts and Scopes are printed ahead of time."),
+        };
+
+        let pretty = format!("{:#?}", vec![value]);
+        let entry = pretty["[\n".len()..pretty.len() - "]".len()].to_string();
+
+        Printed {
+            count,
+            display: value.to_string(),
+            debug: format!("{value:?}"),
+            entry,
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::{collections::HashMap, fmt::Write};
+
+use super::Printed;
+use crate::{
+    util::error::{self, CodegenError},
+    OperationType, Stack, ValueType,
+};
+
+const RUNTIME: &str = include_str!("wasm_runtime.wat");
+
+/// Strings and descriptors start after the scratch memory used for formatting numbers.
+const DATA_START: u32 = 1024;
+const VALUE_STACK_CAPACITY: u32 = 1048576;
+const PAGE_SIZE: u32 = 65536;
+
+/// Strings used by the runtime, a `$name` and a `$name_len` global are generated for each one.
+const RUNTIME_STRINGS: &[(&str, &str)] = &[
+    ("msg_runtime_error", "\x1b[91mRuntime Error\x1b[0m\n"),
+    ("msg_insufficient", "\tNot enough arguments on the stack.\n"),
+    ("msg_mismatched", "\tMismatched types.\n"),
+    ("msg_divide_by_zero", "\tAttempt to divide by zero.\n"),
+    ("msg_overflow", "\tValue stack overflow.\n"),
+    ("str_true", "true"),
+    ("str_false", "false"),
+    ("str_newline", "\n"),
+    ("str_int_open", "Int("),
+    ("str_float_open", "Float("),
+    ("str_bool_open", "Bool("),
+    ("str_close", ")"),
+    ("str_element", " is "),
+    ("str_element_end", " element in the stack\n"),
+    ("str_list_open", "[\n"),
+    ("str_list_close", "]\n"),
+    ("str_int_entry_open", "    Int(\n        "),
+    ("str_float_entry_open", "    Float(\n        "),
+    ("str_bool_entry_open", "    Bool(\n        "),
+    ("str_entry_close", ",\n    ),\n"),
+];
+
+/**
+Compiles the program to a WebAssembly module in the text format.
+
+The module exports its `memory` and a `run` function executing the program.
+It imports the following functions from the `env` module of the host:
+- `print(ptr: i32, len: i32)` writes UTF-8 from memory to the output.
+- `format_float(value: f64, debug: i32, ptr: i32) -> i32` writes the Float formatted like `Display`,
+  or `Debug` if `debug` isn't 0, to memory and returns its length.
+- `pow(base: f64, exponent: f64) -> f64` like `f64::powf`.
+
+Runtime errors are printed and end the execution with a trap.
+Every scope is compiled into a function called through a table, see `wasm_runtime.wat`.
+File operations are not supported.
+*/
+pub fn compile(stack: &[Stack]) -> error::codegen::Result<String> {
+    let mut generator = Generator::default();
+
+    generator.scope("program", stack)?;
+
+    // Results of `!` on Texts and Scopes.
+    let text_empty = generator.descriptor(0, &ValueType::Text(String::new()));
+    let text_true = generator.descriptor(0, &ValueType::Text("true".to_string()));
+    let code = generator.scope("scope_empty_code", &[])?;
+    let scope_empty = generator.descriptor(code, &ValueType::Scope(vec![]));
+    let scope_true = [Stack::Value(ValueType::Bool(true))];
+    let code = generator.scope("scope_true_code", &scope_true)?;
+    let scope_true = generator.descriptor(code, &ValueType::Scope(scope_true.to_vec()));
+
+    let mut globals = String::new();
+    for (name, content) in RUNTIME_STRINGS {
+        let (address, len) = generator.string(content);
+        writeln!(
+            globals,
+            "  (global ${name} i32 (i32.const {address}))\n  (global ${name}_len i32 (i32.const {len}))"
+        )
+        .unwrap();
+    }
+    for (name, address) in [
+        ("text_empty", text_empty),
+        ("text_true", text_true),
+        ("scope_empty", scope_empty),
+        ("scope_true", scope_true),
+    ] {
+        writeln!(globals, "  (global ${name} i32 (i32.const {address}))").unwrap();
+    }
+
+    let stack_base = generator.data_end().next_multiple_of(16);
+    let stack_end = stack_base + VALUE_STACK_CAPACITY * 16;
+    writeln!(
+        globals,
+        "  (global $stack_base i32 (i32.const {stack_base}))\n  (global $stack_end i32 (i32.const {stack_end}))\n  (global $sp (mut i32) (i32.const {stack_base}))"
+    )
+    .unwrap();
+
+    let mut data = String::with_capacity(generator.data.len());
+    for byte in &generator.data {
+        match byte {
+            b'"' | b'\\' => write!(data, "\\{}", *byte as char).unwrap(),
+            b' '..=b'~' => data.push(*byte as char),
+            _ => write!(data, "\\{byte:02x}").unwrap(),
+        }
+    }
+
+    Ok(format!(
+        "(module\n{RUNTIME}\n  ;; ---------------------------------------------------------------------------\n  ;; Program\n  ;; ---------------------------------------------------------------------------\n\n  (memory (export \"memory\") {})\n  (data (i32.const {DATA_START}) \"{data}\")\n{globals}\n  (table {} funcref)\n  (elem (i32.const 0) func {})\n\n{}  (export \"run\" (func $program)))\n",
+        stack_end.div_ceil(PAGE_SIZE),
+        generator.table.len(),
+        generator.table.join(" "),
+        generator.functions
+    ))
+}
+
+/// Same as `compile`, assembled into the binary format.
+pub fn compile_binary(stack: &[Stack]) -> error::codegen::Result<Vec<u8>> {
+    Ok(wat::parse_str(compile(stack)?).expect("Generated modules are valid."))
+}
+
+#[derive(Default)]
+struct Generator {
+    functions: String,
+    /// Functions of the scopes, descriptors refer to them by their index.
+    table: Vec<String>,
+    data: Vec<u8>,
+    strings: HashMap<String, (u32, u32)>,
+    labels: usize,
+}
+
+impl Generator {
+    fn label(&mut self, prefix: &str) -> String {
+        self.labels += 1;
+
+        format!("{prefix}_{}", self.labels)
+    }
+
+    fn data_end(&self) -> u32 {
+        DATA_START + self.data.len() as u32
+    }
+
+    /// Compiles the scope into a function with the given name and gives its index in the table.
+    fn scope(&mut self, label: &str, stack: &[Stack]) -> error::codegen::Result<u32> {
+        let mut body = format!("  (func ${label}\n");
+
+        for element in stack {
+            match element {
+                Stack::Location(location) => writeln!(body, "    ;; {location}").unwrap(),
+                // Declared stack effects are only checked by the interpreter.
+                Stack::Effect(effect) => {
+                    writeln!(body, "    ;; macro {} {effect}", effect.name).unwrap()
+                }
+                Stack::EffectEnd(effect) => {
+                    writeln!(body, "    ;; end of macro {}", effect.name).unwrap()
+                }
+                // Locals aren't supported by this target.
+                Stack::LocalFrame | Stack::LocalFrameEnd => (),
+                Stack::Operation(operation) => {
+                    writeln!(body, "    (call ${})", routine(*operation)?).unwrap()
+                }
+                Stack::Value(ValueType::Int(int)) => {
+                    writeln!(body, "    (call $push_int (i64.const {int}))").unwrap()
+                }
+                Stack::Value(ValueType::Float(float)) => writeln!(
+                    body,
+                    "    (call $push_float (f64.const {}))",
+                    float_literal(*float)
+                )
+                .unwrap(),
+                Stack::Value(ValueType::Bool(condition)) => writeln!(
+                    body,
+                    "    (call $push_bool (i32.const {}))",
+                    *condition as u8
+                )
+                .unwrap(),
+                Stack::Value(value @ ValueType::Text(_)) => {
+                    let descriptor = self.descriptor(0, value);
+
+                    writeln!(
+                        body,
+                        "    (call $push (global.get $TAG_TEXT) (i64.const {descriptor}))"
+                    )
+                    .unwrap()
+                }
+                Stack::Value(value @ ValueType::Scope(scope)) => {
+                    let label = self.label("scope");
+                    let code = self.scope(&label, scope)?;
+                    let descriptor = self.descriptor(code, value);
+
+                    writeln!(
+                        body,
+                        "    (call $push (global.get $TAG_SCOPE) (i64.const {descriptor}))"
+                    )
+                    .unwrap()
+                }
+            }
+        }
+
+        body.push_str("  )\n\n");
+        self.functions.push_str(&body);
+        self.table.push(format!("${label}"));
+
+        Ok(self.table.len() as u32 - 1)
+    }
+
+    /// Describes a Text or a Scope with all the ways it can be printed and gives its address.
+    fn descriptor(&mut self, code: u32, value: &ValueType) -> u32 {
+        let printed = Printed::new(value);
+
+        let display = self.string(&printed.display);
+        let debug = self.string(&printed.debug);
+        let entry = self.string(&printed.entry);
+
+        self.data.resize(self.data.len().next_multiple_of(4), 0);
+        let address = self.data_end();
+
+        for field in [
+            code,
+            printed.count as u32,
+            display.0,
+            display.1,
+            debug.0,
+            debug.1,
+            entry.0,
+            entry.1,
+        ] {
+            self.data.extend_from_slice(&field.to_le_bytes());
+        }
+
+        address
+    }
+
+    /// Gives the address and length of the string, every string is stored once.
+    fn string(&mut self, content: &str) -> (u32, u32) {
+        if let Some(string) = self.strings.get(content) {
+            return *string;
+        }
+
+        let string = (self.data_end(), content.len() as u32);
+        self.data.extend_from_slice(content.as_bytes());
+        self.strings.insert(content.to_string(), string);
+
+        string
+    }
+}
+
+/// Exponent notation is the shortest representation that reads back as the same number.
+fn float_literal(float: f64) -> String {
+    if float.is_nan() {
+        "nan".to_string()
+    } else if float.is_infinite() {
+        format!("{}inf", if float < 0.0 { "-" } else { "" })
+    } else {
+        format!("{float:e}")
+    }
+}
+
+fn routine(operation: OperationType) -> error::codegen::Result<&'static str> {
+    use OperationType::*;
+
+    Ok(match operation {
+        Add => "op_add",
+        Sub => "op_sub",
+        Mul => "op_mul",
+        Div => "op_div",
+        Mod => "op_mod",
+        Pow => "op_pow",
+        Print => "op_print",
+        PrintDebug => "op_print_debug",
+        PrintDebugStack => "op_print_debug_stack",
+        If => "op_if",
+        Lt => "op_lt",
+        Gt => "op_gt",
+        Eq => "op_eq",
+        Leq => "op_leq",
+        Geq => "op_geq",
+        Or => "op_or",
+        And => "op_and",
+        For => "op_for",
+        While => "op_while",
+        Switch => "op_switch",
+        Reverse => "op_reverse",
+        Pop => "op_pop",
+        Not => "op_not",
+        Copy => "op_copy",
+        Println => "op_println",
+        _ => {
+            return Err(CodegenError::Unsupported {
+                target: "wasm".to_string(),
+                what: format!("`{}`", operation.keyword()),
+            })
+        }
+    })
+}
@@ -0,0 +1,519 @@
+  ;; Runtime of programs compiled with `build --target wasm`.
+  ;;
+  ;; Values live on a value stack in linear memory. Every value takes 16 bytes: an i32 tag
+  ;; followed by an i64 payload at offset 8. Ints and Bools are stored directly, Floats as
+  ;; their bits, Texts and Scopes point to a descriptor generated by the compiler.
+  ;;
+  ;; The compiler appends the program, the descriptors, the strings used here and the
+  ;; `$stack_base`, `$stack_end` and `$sp` globals.
+
+  ;; Writes `len` bytes of UTF-8 starting at `ptr` to the output.
+  (import "env" "print" (func $print (param $ptr i32) (param $len i32)))
+  ;; Writes the Float formatted like Rust's `Display`, or `Debug` if `debug` is set, at `ptr`
+  ;; and returns its length. At most 512 bytes are available.
+  (import "env" "format_float" (func $format_float (param $value f64) (param $debug i32) (param $ptr i32) (result i32)))
+  ;; Raises `base` to the power of `exponent` like `f64::powf`.
+  (import "env" "pow" (func $pow (param $base f64) (param $exponent f64) (result f64)))
+
+  (type $code (func))
+
+  (global $TAG_INT i32 (i32.const 0))
+  (global $TAG_FLOAT i32 (i32.const 1))
+  (global $TAG_TEXT i32 (i32.const 2))
+  (global $TAG_SCOPE i32 (i32.const 3))
+  (global $TAG_BOOL i32 (i32.const 4))
+
+  ;; Scratch memory used for formatting numbers.
+  (global $INT_BUFFER_END i32 (i32.const 64))
+  (global $FLOAT_BUFFER i32 (i32.const 64))
+
+  ;; ---------------------------------------------------------------------------
+  ;; Output
+  ;; ---------------------------------------------------------------------------
+
+  (func $write_int (param $value i64)
+    (local $ptr i32)
+    (local $magnitude i64)
+    (local.set $ptr (global.get $INT_BUFFER_END))
+    ;; Negating i64::MIN leaves it unchanged, which is still correct when treated as unsigned.
+    (local.set $magnitude
+      (select
+        (i64.sub (i64.const 0) (local.get $value))
+        (local.get $value)
+        (i64.lt_s (local.get $value) (i64.const 0))))
+    (loop $digits
+      (local.set $ptr (i32.sub (local.get $ptr) (i32.const 1)))
+      (i64.store8 (local.get $ptr)
+        (i64.add (i64.const 48) (i64.rem_u (local.get $magnitude) (i64.const 10))))
+      (local.set $magnitude (i64.div_u (local.get $magnitude) (i64.const 10)))
+      (br_if $digits (i64.ne (local.get $magnitude) (i64.const 0))))
+    (if (i64.lt_s (local.get $value) (i64.const 0))
+      (then
+        (local.set $ptr (i32.sub (local.get $ptr) (i32.const 1)))
+        (i32.store8 (local.get $ptr) (i32.const 45))))
+    (call $print (local.get $ptr) (i32.sub (global.get $INT_BUFFER_END) (local.get $ptr))))
+
+  (func $write_float (param $value i64) (param $debug i32)
+    (call $print
+      (global.get $FLOAT_BUFFER)
+      (call $format_float
+        (f64.reinterpret_i64 (local.get $value))
+        (local.get $debug)
+        (global.get $FLOAT_BUFFER))))
+
+  (func $write_bool (param $value i64)
+    (if (i64.eqz (local.get $value))
+      (then (call $print (global.get $str_false) (global.get $str_false_len)))
+      (else (call $print (global.get $str_true) (global.get $str_true_len)))))
+
+  ;; Writes the string at `offset` of the descriptor.
+  (func $write_descriptor (param $descriptor i64) (param $offset i32)
+    (local $ptr i32)
+    (local.set $ptr (i32.add (i32.wrap_i64 (local.get $descriptor)) (local.get $offset)))
+    (call $print (i32.load (local.get $ptr)) (i32.load offset=4 (local.get $ptr))))
+
+  (func $write_display (param $tag i32) (param $value i64)
+    (block $done
+      (block $descriptor
+        (block $bool
+          (block $float
+            (block $int
+              (br_table $int $float $descriptor $descriptor $bool (local.get $tag)))
+            (call $write_int (local.get $value))
+            (br $done))
+          (call $write_float (local.get $value) (i32.const 0))
+          (br $done))
+        (call $write_bool (local.get $value))
+        (br $done))
+      (call $write_descriptor (local.get $value) (i32.const 8))))
+
+  (func $write_debug (param $tag i32) (param $value i64)
+    (block $done
+      (block $descriptor
+        (block $bool
+          (block $float
+            (block $int
+              (br_table $int $float $descriptor $descriptor $bool (local.get $tag)))
+            (call $print (global.get $str_int_open) (global.get $str_int_open_len))
+            (call $write_int (local.get $value))
+            (call $print (global.get $str_close) (global.get $str_close_len))
+            (br $done))
+          (call $print (global.get $str_float_open) (global.get $str_float_open_len))
+          (call $write_float (local.get $value) (i32.const 1))
+          (call $print (global.get $str_close) (global.get $str_close_len))
+          (br $done))
+        (call $print (global.get $str_bool_open) (global.get $str_bool_open_len))
+        (call $write_bool (local.get $value))
+        (call $print (global.get $str_close) (global.get $str_close_len))
+        (br $done))
+      (call $write_descriptor (local.get $value) (i32.const 16))))
+
+  ;; Writes the value the way it appears in the pretty printed value stack.
+  (func $write_entry (param $tag i32) (param $value i64)
+    (block $done
+      (block $descriptor
+        (block $bool
+          (block $float
+            (block $int
+              (br_table $int $float $descriptor $descriptor $bool (local.get $tag)))
+            (call $print (global.get $str_int_entry_open) (global.get $str_int_entry_open_len))
+            (call $write_int (local.get $value))
+            (call $print (global.get $str_entry_close) (global.get $str_entry_close_len))
+            (br $done))
+          (call $print (global.get $str_float_entry_open) (global.get $str_float_entry_open_len))
+          (call $write_float (local.get $value) (i32.const 1))
+          (call $print (global.get $str_entry_close) (global.get $str_entry_close_len))
+          (br $done))
+        (call $print (global.get $str_bool_entry_open) (global.get $str_bool_entry_open_len))
+        (call $write_bool (local.get $value))
+        (call $print (global.get $str_entry_close) (global.get $str_entry_close_len))
+        (br $done))
+      (call $write_descriptor (local.get $value) (i32.const 24))))
+
+  ;; ---------------------------------------------------------------------------
+  ;; Errors
+  ;; ---------------------------------------------------------------------------
+
+  ;; Prints the message and traps.
+  (func $fail (param $ptr i32) (param $len i32)
+    (call $print (global.get $msg_runtime_error) (global.get $msg_runtime_error_len))
+    (call $print (local.get $ptr) (local.get $len))
+    (unreachable))
+
+  (func $fail_mismatched
+    (call $fail (global.get $msg_mismatched) (global.get $msg_mismatched_len)))
+
+  (func $fail_divide_by_zero
+    (call $fail (global.get $msg_divide_by_zero) (global.get $msg_divide_by_zero_len)))
+
+  ;; ---------------------------------------------------------------------------
+  ;; Value stack
+  ;; ---------------------------------------------------------------------------
+
+  (func $need (param $amount i32)
+    (if (i32.lt_u
+          (i32.shr_u (i32.sub (global.get $sp) (global.get $stack_base)) (i32.const 4))
+          (local.get $amount))
+      (then (call $fail (global.get $msg_insufficient) (global.get $msg_insufficient_len)))))
+
+  (func $push (param $tag i32) (param $value i64)
+    (if (i32.ge_u (global.get $sp) (global.get $stack_end))
+      (then (call $fail (global.get $msg_overflow) (global.get $msg_overflow_len))))
+    (i32.store (global.get $sp) (local.get $tag))
+    (i64.store offset=8 (global.get $sp) (local.get $value))
+    (global.set $sp (i32.add (global.get $sp) (i32.const 16))))
+
+  (func $push_int (param $value i64)
+    (call $push (global.get $TAG_INT) (local.get $value)))
+
+  (func $push_float (param $value f64)
+    (call $push (global.get $TAG_FLOAT) (i64.reinterpret_f64 (local.get $value))))
+
+  (func $push_bool (param $value i32)
+    (call $push (global.get $TAG_BOOL) (i64.extend_i32_u (local.get $value))))
+
+  ;; Removes the last value and gives its address, it stays valid until the next push.
+  (func $pop (result i32)
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 16)))
+    (global.get $sp))
+
+  (func $tag (param $address i32) (result i32)
+    (i32.load (local.get $address)))
+
+  (func $payload (param $address i32) (result i64)
+    (i64.load offset=8 (local.get $address)))
+
+  (func $float (param $address i32) (result f64)
+    (f64.load offset=8 (local.get $address)))
+
+  (func $truthy (param $address i32) (result i32)
+    (local $tag i32)
+    (local.set $tag (call $tag (local.get $address)))
+    (if (result i32) (i32.eq (local.get $tag) (global.get $TAG_FLOAT))
+      (then (f64.ne (call $float (local.get $address)) (f64.const 0)))
+      (else
+        (if (result i32)
+          (i32.or
+            (i32.eq (local.get $tag) (global.get $TAG_TEXT))
+            (i32.eq (local.get $tag) (global.get $TAG_SCOPE)))
+          (then
+            (i32.ne
+              (i32.load offset=4 (i32.wrap_i64 (call $payload (local.get $address))))
+              (i32.const 0)))
+          (else (i64.ne (call $payload (local.get $address)) (i64.const 0)))))))
+
+  ;; Pops two numbers of the same type and gives their type.
+  ;; The next to last value is at `$sp` and the last one at `$sp + 16`.
+  (func $pop_numbers (result i32)
+    (local $tag i32)
+    (call $need (i32.const 2))
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 32)))
+    (local.set $tag (call $tag (global.get $sp)))
+    (if (i32.or
+          (i32.ne (local.get $tag) (call $tag (i32.add (global.get $sp) (i32.const 16))))
+          (i32.and
+            (i32.ne (local.get $tag) (global.get $TAG_INT))
+            (i32.ne (local.get $tag) (global.get $TAG_FLOAT))))
+      (then (call $fail_mismatched)))
+    (local.get $tag))
+
+  (func $next_to_last (result i64)
+    (call $payload (global.get $sp)))
+
+  (func $last (result i64)
+    (call $payload (i32.add (global.get $sp) (i32.const 16))))
+
+  (func $next_to_last_float (result f64)
+    (call $float (global.get $sp)))
+
+  (func $last_float (result f64)
+    (call $float (i32.add (global.get $sp) (i32.const 16))))
+
+  ;; Remainder of the division truncated towards zero, like `%` on `f64`.
+  ;; The largest multiple of `y` that fits is subtracted over and over, every subtraction is exact.
+  (func $fmod (param $x f64) (param $y f64) (result f64)
+    (local $rest f64)
+    (local $divisor f64)
+    (local $multiple f64)
+    (if (i32.or
+          (i32.or (f64.ne (local.get $x) (local.get $x)) (f64.ne (local.get $y) (local.get $y)))
+          (i32.or
+            (f64.eq (f64.abs (local.get $x)) (f64.const inf))
+            (f64.eq (local.get $y) (f64.const 0))))
+      (then (return (f64.const nan))))
+    (local.set $rest (f64.abs (local.get $x)))
+    (local.set $divisor (f64.abs (local.get $y)))
+    (block $done
+      (loop $subtract
+        (br_if $done (f64.lt (local.get $rest) (local.get $divisor)))
+        (local.set $multiple (local.get $divisor))
+        (block $largest
+          (loop $double
+            (br_if $largest
+              (i32.eqz (f64.le (f64.mul (local.get $multiple) (f64.const 2)) (local.get $rest))))
+            (local.set $multiple (f64.mul (local.get $multiple) (f64.const 2)))
+            (br $double)))
+        (local.set $rest (f64.sub (local.get $rest) (local.get $multiple)))
+        (br $subtract)))
+    (f64.copysign (local.get $rest) (local.get $x)))
+
+  ;; ---------------------------------------------------------------------------
+  ;; Operations
+  ;; ---------------------------------------------------------------------------
+
+  ;; Ints wrap around on overflow.
+  (func $op_add
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_INT))
+      (then (call $push_int (i64.add (call $next_to_last) (call $last))))
+      (else (call $push_float (f64.add (call $next_to_last_float) (call $last_float))))))
+
+  (func $op_sub
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_INT))
+      (then (call $push_int (i64.sub (call $next_to_last) (call $last))))
+      (else (call $push_float (f64.sub (call $next_to_last_float) (call $last_float))))))
+
+  (func $op_mul
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_INT))
+      (then (call $push_int (i64.mul (call $next_to_last) (call $last))))
+      (else (call $push_float (f64.mul (call $next_to_last_float) (call $last_float))))))
+
+  (func $op_div
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_FLOAT))
+      (then
+        (call $push_float (f64.div (call $next_to_last_float) (call $last_float)))
+        (return)))
+    (if (i64.eqz (call $last))
+      (then (call $fail_divide_by_zero)))
+    (if (i64.eq (call $last) (i64.const -1))
+      (then (call $push_int (i64.sub (i64.const 0) (call $next_to_last))))
+      (else (call $push_int (i64.div_s (call $next_to_last) (call $last))))))
+
+  (func $op_mod
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_FLOAT))
+      (then
+        (call $push_float (call $fmod (call $next_to_last_float) (call $last_float)))
+        (return)))
+    (if (i64.eqz (call $last))
+      (then (call $fail_divide_by_zero)))
+    (if (i64.eq (call $last) (i64.const -1))
+      (then (call $push_int (i64.const 0)))
+      (else (call $push_int (i64.rem_s (call $next_to_last) (call $last))))))
+
+  (func $op_pow
+    (local $base i64)
+    (local $exponent i64)
+    (local $result i64)
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_FLOAT))
+      (then
+        (call $push_float (call $pow (call $next_to_last_float) (call $last_float)))
+        (return)))
+    (local.set $base (call $next_to_last))
+    ;; The exponent is truncated to u32 like in the interpreter.
+    (local.set $exponent (i64.and (call $last) (i64.const 0xffffffff)))
+    (local.set $result (i64.const 1))
+    (block $done
+      (loop $square
+        (br_if $done (i64.eqz (local.get $exponent)))
+        (if (i64.ne (i64.and (local.get $exponent) (i64.const 1)) (i64.const 0))
+          (then (local.set $result (i64.mul (local.get $result) (local.get $base)))))
+        (local.set $base (i64.mul (local.get $base) (local.get $base)))
+        (local.set $exponent (i64.shr_u (local.get $exponent) (i64.const 1)))
+        (br $square)))
+    (call $push_int (local.get $result)))
+
+  ;; Comparisons compare the last value with the next to last one.
+  (func $op_lt
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_INT))
+      (then (call $push_bool (i64.lt_s (call $last) (call $next_to_last))))
+      (else (call $push_bool (f64.lt (call $last_float) (call $next_to_last_float))))))
+
+  (func $op_gt
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_INT))
+      (then (call $push_bool (i64.gt_s (call $last) (call $next_to_last))))
+      (else (call $push_bool (f64.gt (call $last_float) (call $next_to_last_float))))))
+
+  (func $op_eq
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_INT))
+      (then (call $push_bool (i64.eq (call $last) (call $next_to_last))))
+      (else (call $push_bool (f64.eq (call $last_float) (call $next_to_last_float))))))
+
+  (func $op_leq
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_INT))
+      (then (call $push_bool (i64.le_s (call $last) (call $next_to_last))))
+      (else (call $push_bool (f64.le (call $last_float) (call $next_to_last_float))))))
+
+  (func $op_geq
+    (if (i32.eq (call $pop_numbers) (global.get $TAG_INT))
+      (then (call $push_bool (i64.ge_s (call $last) (call $next_to_last))))
+      (else (call $push_bool (f64.ge (call $last_float) (call $next_to_last_float))))))
+
+  (func $op_or
+    (local $last i32)
+    (call $need (i32.const 2))
+    (local.set $last (call $truthy (call $pop)))
+    (call $push_bool (i32.or (local.get $last) (call $truthy (call $pop)))))
+
+  (func $op_and
+    (local $last i32)
+    (call $need (i32.const 2))
+    (local.set $last (call $truthy (call $pop)))
+    (call $push_bool (i32.and (local.get $last) (call $truthy (call $pop)))))
+
+  (func $op_not
+    (local $value i32)
+    (local $tag i32)
+    (local $truthy i32)
+    (call $need (i32.const 1))
+    (local.set $value (call $pop))
+    (local.set $tag (call $tag (local.get $value)))
+    (local.set $truthy (call $truthy (local.get $value)))
+    (block $done
+      (block $scope
+        (block $text
+          (block $bool
+            (block $float
+              (block $int
+                (br_table $int $float $text $scope $bool (local.get $tag)))
+              (call $push_int (i64.extend_i32_u (i32.eqz (local.get $truthy))))
+              (br $done))
+            (call $push_float
+              (select (f64.const 0) (f64.const 1) (local.get $truthy)))
+            (br $done))
+          (call $push_bool (i32.eqz (local.get $truthy)))
+          (br $done))
+        (call $push
+          (global.get $TAG_TEXT)
+          (i64.extend_i32_u
+            (select (global.get $text_empty) (global.get $text_true) (local.get $truthy))))
+        (br $done))
+      (call $push
+        (global.get $TAG_SCOPE)
+        (i64.extend_i32_u
+          (select (global.get $scope_empty) (global.get $scope_true) (local.get $truthy))))))
+
+  (func $run_scope (param $address i32)
+    (call_indirect (type $code)
+      (i32.load (i32.wrap_i64 (call $payload (local.get $address))))))
+
+  (func $op_if
+    (local $scope i32)
+    (call $need (i32.const 2))
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 32)))
+    (local.set $scope (global.get $sp))
+    (if (call $truthy (i32.add (local.get $scope) (i32.const 16)))
+      (then
+        (if (i32.ne (call $tag (local.get $scope)) (global.get $TAG_SCOPE))
+          (then (call $fail_mismatched)))
+        (call $run_scope (local.get $scope)))))
+
+  (func $op_for
+    (local $code i32)
+    (local $range i64)
+    (call $need (i32.const 2))
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 32)))
+    (if (i32.ne (call $tag (i32.add (global.get $sp) (i32.const 16))) (global.get $TAG_INT))
+      (then (call $fail_mismatched)))
+    (if (i32.ne (call $tag (global.get $sp)) (global.get $TAG_SCOPE))
+      (then (return)))
+    (local.set $code (i32.load (i32.wrap_i64 (call $payload (global.get $sp)))))
+    (local.set $range (call $payload (i32.add (global.get $sp) (i32.const 16))))
+    (block $done
+      (loop $iteration
+        (br_if $done (i64.le_s (local.get $range) (i64.const 0)))
+        (call_indirect (type $code) (local.get $code))
+        (local.set $range (i64.sub (local.get $range) (i64.const 1)))
+        (br $iteration))))
+
+  (func $op_while
+    (local $code i32)
+    (local $is_scope i32)
+    (call $need (i32.const 2))
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 32)))
+    (local.set $is_scope (i32.eq (call $tag (global.get $sp)) (global.get $TAG_SCOPE)))
+    (if (local.get $is_scope)
+      (then (local.set $code (i32.load (i32.wrap_i64 (call $payload (global.get $sp)))))))
+    ;; The condition stays on the stack.
+    (call $push
+      (call $tag (i32.add (global.get $sp) (i32.const 16)))
+      (call $payload (i32.add (global.get $sp) (i32.const 16))))
+    (if (i32.eqz (local.get $is_scope))
+      (then (return)))
+    (block $done
+      (loop $iteration
+        (call $need (i32.const 1))
+        (br_if $done (i32.eqz (call $truthy (i32.sub (global.get $sp) (i32.const 16)))))
+        (call_indirect (type $code) (local.get $code))
+        (br $iteration))))
+
+  (func $op_print
+    (local $last i32)
+    (call $need (i32.const 1))
+    (local.set $last (i32.sub (global.get $sp) (i32.const 16)))
+    (call $write_display (call $tag (local.get $last)) (call $payload (local.get $last))))
+
+  (func $op_println
+    (call $op_print)
+    (call $print (global.get $str_newline) (global.get $str_newline_len)))
+
+  (func $op_print_debug
+    (local $last i32)
+    (call $need (i32.const 1))
+    (local.set $last (i32.sub (global.get $sp) (i32.const 16)))
+    (call $write_debug (call $tag (local.get $last)) (call $payload (local.get $last)))
+    (call $print (global.get $str_element) (global.get $str_element_len))
+    (call $write_int
+      (i64.extend_i32_u
+        (i32.shr_u (i32.sub (global.get $sp) (global.get $stack_base)) (i32.const 4))))
+    (call $print (global.get $str_element_end) (global.get $str_element_end_len)))
+
+  (func $op_print_debug_stack
+    (local $address i32)
+    (call $need (i32.const 1))
+    (call $print (global.get $str_list_open) (global.get $str_list_open_len))
+    (local.set $address (global.get $stack_base))
+    (block $done
+      (loop $entry
+        (br_if $done (i32.ge_u (local.get $address) (global.get $sp)))
+        (call $write_entry (call $tag (local.get $address)) (call $payload (local.get $address)))
+        (local.set $address (i32.add (local.get $address) (i32.const 16)))
+        (br $entry)))
+    (call $print (global.get $str_list_close) (global.get $str_list_close_len)))
+
+  (func $swap (param $first i32) (param $second i32)
+    (local $tag i32)
+    (local $value i64)
+    (local.set $tag (call $tag (local.get $first)))
+    (local.set $value (call $payload (local.get $first)))
+    (i32.store (local.get $first) (call $tag (local.get $second)))
+    (i64.store offset=8 (local.get $first) (call $payload (local.get $second)))
+    (i32.store (local.get $second) (local.get $tag))
+    (i64.store offset=8 (local.get $second) (local.get $value)))
+
+  (func $op_switch
+    (call $need (i32.const 2))
+    (call $swap
+      (i32.sub (global.get $sp) (i32.const 32))
+      (i32.sub (global.get $sp) (i32.const 16))))
+
+  (func $op_reverse
+    (local $first i32)
+    (local $second i32)
+    (local.set $first (global.get $stack_base))
+    (local.set $second (i32.sub (global.get $sp) (i32.const 16)))
+    (block $done
+      (loop $pair
+        (br_if $done (i32.ge_s (local.get $first) (local.get $second)))
+        (call $swap (local.get $first) (local.get $second))
+        (local.set $first (i32.add (local.get $first) (i32.const 16)))
+        (local.set $second (i32.sub (local.get $second) (i32.const 16)))
+        (br $pair))))
+
+  (func $op_pop
+    (if (i32.gt_u (global.get $sp) (global.get $stack_base))
+      (then (drop (call $pop)))))
+
+  (func $op_copy
+    (local $last i32)
+    (call $need (i32.const 1))
+    (local.set $last (i32.sub (global.get $sp) (i32.const 16)))
+    (call $push (call $tag (local.get $last)) (call $payload (local.get $last))))
@@ -0,0 +1,309 @@
+use std::{collections::HashMap, fmt::Write};
+
+use super::Printed;
+use crate::{
+    util::error::{self, CodegenError},
+    OperationType, Stack, ValueType,
+};
+
+const RUNTIME: &str = include_str!("x86_64_runtime.s");
+
+/// Registers holding the Ints on top of the stack, none of them survives a call.
+const CACHE: [&str; 6] = ["rdi", "rsi", "r8", "r9", "r10", "r11"];
+
+/**
+Lowers the program to x86-64 Linux assembly in GAS (Intel) syntax.
+
+The output is self-contained and only needs an assembler and a linker:
+```sh
+as program.s -o program.o && ld program.o -o program
+```
+
+Every scope is compiled into a function and values are kept on a value stack in memory, see `x86_64_runtime.s`.
+Ints pushed by the scope stay in registers until something else needs the stack, so arithmetic and
+stack shuffling on them don't touch memory.
+Floats and file operations are not supported.
+*/
+pub fn compile(stack: &[Stack]) -> error::codegen::Result<String> {
+    let mut generator = Generator::default();
+
+    generator.scope("program".to_string(), stack)?;
+
+    // Results of `!` on Texts and Scopes.
+    generator.descriptor(
+        "text_empty".to_string(),
+        "0",
+        &ValueType::Text(String::new()),
+    );
+    generator.descriptor(
+        "text_true".to_string(),
+        "0",
+        &ValueType::Text("true".to_string()),
+    );
+    generator.scope("scope_empty_code".to_string(), &[])?;
+    generator.descriptor(
+        "scope_empty".to_string(),
+        "scope_empty_code",
+        &ValueType::Scope(vec![]),
+    );
+    let scope_true = [Stack::Value(ValueType::Bool(true))];
+    generator.scope("scope_true_code".to_string(), &scope_true)?;
+    generator.descriptor(
+        "scope_true".to_string(),
+        "scope_true_code",
+        &ValueType::Scope(scope_true.to_vec()),
+    );
+
+    Ok(format!(
+        "{RUNTIME}\n# ---------------------------------------------------------------------------\n# Program\n# ---------------------------------------------------------------------------\n\n    .section .text\n{}\n    .section .rodata\n    .balign 8\n{}",
+        generator.text, generator.data
+    ))
+}
+
+#[derive(Default)]
+struct Generator {
+    text: String,
+    data: String,
+    strings: HashMap<String, String>,
+    labels: usize,
+}
+
+impl Generator {
+    fn label(&mut self, prefix: &str) -> String {
+        self.labels += 1;
+
+        format!("{prefix}_{}", self.labels)
+    }
+
+    /// Compiles the scope into a function with the given label.
+    fn scope(&mut self, label: String, stack: &[Stack]) -> error::codegen::Result<()> {
+        let mut body = format!("{label}:\n");
+        // Registers of the cached Ints, the last one is the top of the stack.
+        let mut cached: Vec<&str> = Vec::new();
+
+        for element in stack {
+            match element {
+                Stack::Location(location) => writeln!(body, "    # {location}").unwrap(),
+                // Declared stack effects are only checked by the interpreter.
+                Stack::Effect(effect) => {
+                    writeln!(body, "    # macro {} {effect}", effect.name).unwrap()
+                }
+                Stack::EffectEnd(effect) => {
+                    writeln!(body, "    # end of macro {}", effect.name).unwrap()
+                }
+                // Locals aren't supported by this target.
+                Stack::LocalFrame | Stack::LocalFrameEnd => (),
+                Stack::Value(ValueType::Int(int)) => {
+                    if cached.len() == CACHE.len() {
+                        flush(&mut body, &mut cached);
+                    }
+                    let register = free_register(&cached);
+                    writeln!(body, "    mov {register}, {int}").unwrap();
+                    cached.push(register);
+                }
+                Stack::Operation(operation) => match (operation, cached.len()) {
+                    (OperationType::Add | OperationType::Sub | OperationType::Mul, 2..) => {
+                        let last = cached.pop().unwrap();
+                        let instruction = match operation {
+                            OperationType::Add => "add",
+                            OperationType::Sub => "sub",
+                            _ => "imul",
+                        };
+                        writeln!(body, "    {instruction} {}, {last}", cached.last().unwrap())
+                            .unwrap();
+                    }
+                    (OperationType::Copy, 1..) if cached.len() < CACHE.len() => {
+                        let register = free_register(&cached);
+                        writeln!(body, "    mov {register}, {}", cached.last().unwrap()).unwrap();
+                        cached.push(register);
+                    }
+                    (OperationType::Pop, 1..) => {
+                        cached.pop();
+                    }
+                    (OperationType::Switch, 2..) => {
+                        let len = cached.len();
+                        cached.swap(len - 2, len - 1);
+                    }
+                    _ => {
+                        flush(&mut body, &mut cached);
+                        writeln!(body, "    call {}", routine(*operation)?).unwrap()
+                    }
+                },
+                Stack::Value(value) => {
+                    flush(&mut body, &mut cached);
+                    self.value(&mut body, value)?;
+                }
+            }
+        }
+
+        flush(&mut body, &mut cached);
+        body.push_str("    ret\n\n");
+        self.text.push_str(&body);
+
+        Ok(())
+    }
+
+    /// Pushes a value that isn't cached in a register.
+    fn value(&mut self, body: &mut String, value: &ValueType) -> error::codegen::Result<()> {
+        match value {
+            ValueType::Int(int) => write!(
+                body,
+                "    mov rax, {int}\n    mov ecx, TAG_INT\n    call rt_push\n"
+            )
+            .unwrap(),
+            ValueType::Bool(condition) => write!(
+                body,
+                "    mov eax, {}\n    mov ecx, TAG_BOOL\n    call rt_push\n",
+                *condition as u8
+            )
+            .unwrap(),
+            ValueType::Text(_) => {
+                let descriptor = self.label("text");
+                self.descriptor(descriptor.clone(), "0", value);
+
+                write!(
+                    body,
+                    "    lea rax, [rip + {descriptor}]\n    mov ecx, TAG_TEXT\n    call rt_push\n"
+                )
+                .unwrap()
+            }
+            ValueType::Scope(scope) => {
+                let code = self.label("scope");
+                self.scope(code.clone(), scope)?;
+                let descriptor = format!("{code}_descriptor");
+                self.descriptor(descriptor.clone(), &code, value);
+
+                write!(
+                    body,
+                    "    lea rax, [rip + {descriptor}]\n    mov ecx, TAG_SCOPE\n    call rt_push\n"
+                )
+                .unwrap()
+            }
+            ValueType::Float(_) => {
+                return Err(CodegenError::Unsupported {
+                    target: "x86_64-asm".to_string(),
+                    what: "Float".to_string(),
+                })
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describes a Text or a Scope with all the ways it can be printed.
+    fn descriptor(&mut self, label: String, code: &str, value: &ValueType) {
+        let printed = Printed::new(value);
+
+        let display = self.string(&printed.display);
+        let debug = self.string(&printed.debug);
+        let entry = self.string(&printed.entry);
+
+        writeln!(
+            self.data,
+            "{label}:\n    .quad {code}, {}\n    .quad {display}\n    .quad {debug}\n    .quad {entry}",
+            printed.count
+        )
+        .unwrap();
+    }
+
+    /// Gives `label, length` of the string, every string is emitted once.
+    fn string(&mut self, content: &str) -> String {
+        if let Some(label) = self.strings.get(content) {
+            return format!("{label}, {}", content.len());
+        }
+
+        let label = self.label("string");
+        let mut escaped = String::with_capacity(content.len());
+        for byte in content.bytes() {
+            match byte {
+                b'"' | b'\\' => write!(escaped, "\\{}", byte as char).unwrap(),
+                b' '..=b'~' => escaped.push(byte as char),
+                _ => write!(escaped, "\\{byte:03o}").unwrap(),
+            }
+        }
+        writeln!(self.data, "{label}: .ascii \"{escaped}\"").unwrap();
+
+        self.strings.insert(content.to_string(), label.clone());
+
+        format!("{label}, {}", content.len())
+    }
+}
+
+fn free_register(cached: &[&str]) -> &'static str {
+    CACHE
+        .into_iter()
+        .find(|register| !cached.contains(register))
+        .unwrap()
+}
+
+/// Moves the cached Ints to the value stack, deepest first.
+fn flush(body: &mut String, cached: &mut Vec<&str>) {
+    match cached[..] {
+        [] => return,
+        [register] => {
+            write!(
+                body,
+                "    mov rax, {register}\n    mov ecx, TAG_INT\n    call rt_push\n"
+            )
+            .unwrap();
+            cached.clear();
+            return;
+        }
+        _ => (),
+    }
+
+    let size = cached.len() * 16;
+    write!(
+        body,
+        "    lea rax, [r12 + {size}]\n    lea rdx, [rip + value_stack_end]\n    cmp rax, rdx\n    ja rt_fail_overflow\n"
+    )
+    .unwrap();
+    for (i, register) in cached.drain(..).enumerate() {
+        write!(
+            body,
+            "    mov qword ptr [r12 + {}], TAG_INT\n    mov [r12 + {}], {register}\n",
+            i * 16,
+            i * 16 + 8
+        )
+        .unwrap();
+    }
+    writeln!(body, "    add r12, {size}").unwrap();
+}
+
+fn routine(operation: OperationType) -> error::codegen::Result<&'static str> {
+    use OperationType::*;
+
+    Ok(match operation {
+        Add => "op_add",
+        Sub => "op_sub",
+        Mul => "op_mul",
+        Div => "op_div",
+        Mod => "op_mod",
+        Pow => "op_pow",
+        Print => "op_print",
+        PrintDebug => "op_print_debug",
+        PrintDebugStack => "op_print_debug_stack",
+        If => "op_if",
+        Lt => "op_lt",
+        Gt => "op_gt",
+        Eq => "op_eq",
+        Leq => "op_leq",
+        Geq => "op_geq",
+        Or => "op_or",
+        And => "op_and",
+        For => "op_for",
+        While => "op_while",
+        Switch => "op_switch",
+        Reverse => "op_reverse",
+        Pop => "op_pop",
+        Not => "op_not",
+        Copy => "op_copy",
+        Println => "op_println",
+        _ => {
+            return Err(CodegenError::Unsupported {
+                target: "x86_64-asm".to_string(),
+                what: format!("`{}`", operation.keyword()),
+            })
+        }
+    })
+}
@@ -0,0 +1,613 @@
+    # Runtime of programs compiled with `build --target x86_64-asm`.
+    #
+    # Values live on a separate value stack pointed to by r12. Every value takes 16 bytes:
+    # a tag followed by the payload. Ints and Bools are stored directly, Texts and Scopes
+    # point to a descriptor generated by the compiler.
+    #
+    # Routines may clobber every register except r12-r15 and rbx.
+
+    .intel_syntax noprefix
+
+    .equ TAG_INT, 0
+    .equ TAG_BOOL, 1
+    .equ TAG_TEXT, 2
+    .equ TAG_SCOPE, 3
+
+    .equ VALUE_STACK_CAPACITY, 1048576
+    .equ OUT_CAPACITY, 65536
+
+    # Descriptor layout, every string is a pointer followed by its length.
+    .equ DESC_CODE, 0
+    .equ DESC_COUNT, 8
+    .equ DESC_DISPLAY, 16
+    .equ DESC_DISPLAY_LEN, 24
+    .equ DESC_DEBUG, 32
+    .equ DESC_DEBUG_LEN, 40
+    .equ DESC_ENTRY, 48
+    .equ DESC_ENTRY_LEN, 56
+
+    .macro STRING name, text
+\name: .ascii "\text"
+    .equ \name\()_len, . - \name
+    .endm
+
+    .macro WRITE name
+    lea rsi, [rip + \name]
+    mov edx, \name\()_len
+    call rt_write
+    .endm
+
+    .macro FAIL name
+    lea rsi, [rip + \name]
+    mov edx, \name\()_len
+    jmp rt_fail
+    .endm
+
+    .macro NEED amount
+    mov ecx, \amount
+    call rt_need
+    .endm
+
+    .section .bss
+    .balign 16
+value_stack: .skip VALUE_STACK_CAPACITY * 16
+value_stack_end:
+out_buffer: .skip OUT_CAPACITY
+out_len: .skip 8
+int_buffer: .skip 32
+
+    .section .rodata
+    STRING msg_runtime_error, "\033[91mRuntime Error\033[0m\n"
+    STRING msg_insufficient, "\tNot enough arguments on the stack.\n"
+    STRING msg_mismatched, "\tMismatched types.\n"
+    STRING msg_divide_by_zero, "\tAttempt to divide by zero.\n"
+    STRING msg_overflow, "\tValue stack overflow.\n"
+    STRING str_true, "true"
+    STRING str_false, "false"
+    STRING str_newline, "\n"
+    STRING str_int_open, "Int("
+    STRING str_bool_open, "Bool("
+    STRING str_close, ")"
+    STRING str_element, " is "
+    STRING str_element_end, " element in the stack\n"
+    STRING str_list_open, "[\n"
+    STRING str_list_close, "]\n"
+    STRING str_int_entry_open, "    Int(\n        "
+    STRING str_bool_entry_open, "    Bool(\n        "
+    STRING str_entry_close, ",\n    ),\n"
+
+    .section .text
+    .globl _start
+_start:
+    lea r12, [rip + value_stack]
+    call program
+    call rt_flush
+    mov eax, 60
+    xor edi, edi
+    syscall
+
+# ---------------------------------------------------------------------------
+# Output
+# ---------------------------------------------------------------------------
+
+# rsi: pointer, rdx: length
+rt_write_all:
+    test rdx, rdx
+    jz 2f
+1:
+    mov eax, 1
+    mov edi, 1
+    syscall
+    test rax, rax
+    jle rt_exit_failure
+    add rsi, rax
+    sub rdx, rax
+    jnz 1b
+2:
+    ret
+
+rt_flush:
+    lea rsi, [rip + out_buffer]
+    mov rdx, [rip + out_len]
+    call rt_write_all
+    mov qword ptr [rip + out_len], 0
+    ret
+
+# rsi: pointer, rdx: length
+rt_write:
+    mov rax, [rip + out_len]
+    lea rcx, [rax + rdx]
+    cmp rcx, OUT_CAPACITY
+    jbe 2f
+    push rsi
+    push rdx
+    call rt_flush
+    pop rdx
+    pop rsi
+    xor eax, eax
+    cmp rdx, OUT_CAPACITY
+    jbe 2f
+    jmp rt_write_all
+2:
+    lea rdi, [rip + out_buffer]
+    add rdi, rax
+    mov rcx, rdx
+    rep movsb
+    add rax, rdx
+    mov [rip + out_len], rax
+    ret
+
+# rax: signed integer
+rt_write_int:
+    lea rdi, [rip + int_buffer + 32]
+    mov rsi, rdi
+    mov r8, rax
+    test rax, rax
+    jns 1f
+    # Negating i64::MIN leaves it unchanged, which is still correct when treated as unsigned.
+    neg rax
+1:
+    xor edx, edx
+    mov ecx, 10
+    div rcx
+    add dl, '0'
+    dec rsi
+    mov [rsi], dl
+    test rax, rax
+    jnz 1b
+    test r8, r8
+    jns 2f
+    dec rsi
+    mov byte ptr [rsi], '-'
+2:
+    mov rdx, rdi
+    sub rdx, rsi
+    jmp rt_write
+
+# rdi: tag, rsi: payload
+rt_write_display:
+    cmp rdi, TAG_INT
+    je 1f
+    cmp rdi, TAG_BOOL
+    je 2f
+    mov rdx, [rsi + DESC_DISPLAY_LEN]
+    mov rsi, [rsi + DESC_DISPLAY]
+    jmp rt_write
+1:
+    mov rax, rsi
+    jmp rt_write_int
+2:
+    test rsi, rsi
+    jz 3f
+    WRITE str_true
+    ret
+3:
+    WRITE str_false
+    ret
+
+# rdi: tag, rsi: payload
+rt_write_debug:
+    cmp rdi, TAG_INT
+    je 1f
+    cmp rdi, TAG_BOOL
+    je 2f
+    mov rdx, [rsi + DESC_DEBUG_LEN]
+    mov rsi, [rsi + DESC_DEBUG]
+    jmp rt_write
+1:
+    push rsi
+    WRITE str_int_open
+    pop rax
+    call rt_write_int
+    WRITE str_close
+    ret
+2:
+    push rsi
+    WRITE str_bool_open
+    pop rsi
+    mov edi, TAG_BOOL
+    call rt_write_display
+    WRITE str_close
+    ret
+
+# rdi: tag, rsi: payload
+# Writes the value the way it appears in the pretty printed value stack.
+rt_write_entry:
+    cmp rdi, TAG_INT
+    je 1f
+    cmp rdi, TAG_BOOL
+    je 2f
+    mov rdx, [rsi + DESC_ENTRY_LEN]
+    mov rsi, [rsi + DESC_ENTRY]
+    jmp rt_write
+1:
+    push rsi
+    WRITE str_int_entry_open
+    pop rax
+    call rt_write_int
+    WRITE str_entry_close
+    ret
+2:
+    push rsi
+    WRITE str_bool_entry_open
+    pop rsi
+    mov edi, TAG_BOOL
+    call rt_write_display
+    WRITE str_entry_close
+    ret
+
+# ---------------------------------------------------------------------------
+# Errors
+# ---------------------------------------------------------------------------
+
+# rsi: message, rdx: length
+rt_fail:
+    push rsi
+    push rdx
+    WRITE msg_runtime_error
+    pop rdx
+    pop rsi
+    call rt_write
+    call rt_flush
+rt_exit_failure:
+    mov eax, 60
+    mov edi, 1
+    syscall
+
+rt_fail_insufficient:
+    FAIL msg_insufficient
+
+rt_fail_mismatched:
+    FAIL msg_mismatched
+
+rt_fail_divide_by_zero:
+    FAIL msg_divide_by_zero
+
+rt_fail_overflow:
+    FAIL msg_overflow
+
+# ---------------------------------------------------------------------------
+# Value stack
+# ---------------------------------------------------------------------------
+
+# rcx: needed amount of values
+rt_need:
+    mov rax, r12
+    lea rdx, [rip + value_stack]
+    sub rax, rdx
+    shr rax, 4
+    cmp rax, rcx
+    jb rt_fail_insufficient
+    ret
+
+# rax: payload, rcx: tag
+rt_push:
+    lea rdx, [rip + value_stack_end]
+    cmp r12, rdx
+    jae rt_fail_overflow
+    mov [r12], rcx
+    mov [r12 + 8], rax
+    add r12, 16
+    ret
+
+rt_push_int:
+    mov ecx, TAG_INT
+    jmp rt_push
+
+rt_push_bool:
+    mov ecx, TAG_BOOL
+    jmp rt_push
+
+# Pops two Ints, rax: next to last value, rcx: last value
+rt_pop_ints:
+    NEED 2
+    sub r12, 32
+    cmp qword ptr [r12], TAG_INT
+    jne rt_fail_mismatched
+    cmp qword ptr [r12 + 16], TAG_INT
+    jne rt_fail_mismatched
+    mov rax, [r12 + 8]
+    mov rcx, [r12 + 24]
+    ret
+
+# rdi: tag, rsi: payload, returns 1 in rax if the value is truthy
+rt_truthy:
+    cmp rdi, TAG_TEXT
+    je 1f
+    cmp rdi, TAG_SCOPE
+    je 2f
+    jmp 3f
+1:
+    mov rsi, [rsi + DESC_DISPLAY_LEN]
+    jmp 3f
+2:
+    mov rsi, [rsi + DESC_COUNT]
+3:
+    xor eax, eax
+    test rsi, rsi
+    setnz al
+    ret
+
+# ---------------------------------------------------------------------------
+# Operations
+# ---------------------------------------------------------------------------
+
+op_add:
+    call rt_pop_ints
+    add rax, rcx
+    jmp rt_push_int
+
+op_sub:
+    call rt_pop_ints
+    sub rax, rcx
+    jmp rt_push_int
+
+op_mul:
+    call rt_pop_ints
+    imul rax, rcx
+    jmp rt_push_int
+
+op_div:
+    call rt_pop_ints
+    test rcx, rcx
+    jz rt_fail_divide_by_zero
+    cmp rcx, -1
+    je 1f
+    cqo
+    idiv rcx
+    jmp rt_push_int
+1:
+    neg rax
+    jmp rt_push_int
+
+op_mod:
+    call rt_pop_ints
+    test rcx, rcx
+    jz rt_fail_divide_by_zero
+    cmp rcx, -1
+    je 1f
+    cqo
+    idiv rcx
+    mov rax, rdx
+    jmp rt_push_int
+1:
+    xor eax, eax
+    jmp rt_push_int
+
+op_pow:
+    call rt_pop_ints
+    # The exponent is truncated to u32 like in the interpreter.
+    mov ecx, ecx
+    mov r8, 1
+1:
+    test rcx, 1
+    jz 2f
+    imul r8, rax
+2:
+    shr rcx, 1
+    jz 3f
+    imul rax, rax
+    jmp 1b
+3:
+    mov rax, r8
+    jmp rt_push_int
+
+# Comparisons compare the last value with the next to last one.
+    .macro COMPARISON name, condition
+\name:
+    call rt_pop_ints
+    cmp rcx, rax
+    set\condition al
+    movzx eax, al
+    jmp rt_push_bool
+    .endm
+
+    COMPARISON op_lt, l
+    COMPARISON op_gt, g
+    COMPARISON op_eq, e
+    COMPARISON op_leq, le
+    COMPARISON op_geq, ge
+
+op_or:
+    NEED 2
+    sub r12, 32
+    mov rdi, [r12 + 16]
+    mov rsi, [r12 + 24]
+    call rt_truthy
+    mov r8, rax
+    mov rdi, [r12]
+    mov rsi, [r12 + 8]
+    push r8
+    call rt_truthy
+    pop r8
+    or rax, r8
+    jmp rt_push_bool
+
+op_and:
+    NEED 2
+    sub r12, 32
+    mov rdi, [r12 + 16]
+    mov rsi, [r12 + 24]
+    call rt_truthy
+    mov r8, rax
+    mov rdi, [r12]
+    mov rsi, [r12 + 8]
+    push r8
+    call rt_truthy
+    pop r8
+    and rax, r8
+    jmp rt_push_bool
+
+op_not:
+    NEED 1
+    mov rdi, [r12 - 16]
+    mov rsi, [r12 - 8]
+    call rt_truthy
+    mov rcx, [r12 - 16]
+    sub r12, 16
+    cmp rcx, TAG_TEXT
+    je 1f
+    cmp rcx, TAG_SCOPE
+    je 2f
+    xor rax, 1
+    jmp rt_push
+1:
+    lea rdx, [rip + text_empty]
+    lea r8, [rip + text_true]
+    jmp 3f
+2:
+    lea rdx, [rip + scope_empty]
+    lea r8, [rip + scope_true]
+3:
+    test rax, rax
+    mov rax, r8
+    cmovnz rax, rdx
+    jmp rt_push
+
+op_if:
+    NEED 2
+    sub r12, 32
+    mov rdi, [r12 + 16]
+    mov rsi, [r12 + 24]
+    call rt_truthy
+    test rax, rax
+    jz 1f
+    cmp qword ptr [r12], TAG_SCOPE
+    jne rt_fail_mismatched
+    mov rax, [r12 + 8]
+    jmp [rax + DESC_CODE]
+1:
+    ret
+
+op_for:
+    NEED 2
+    sub r12, 32
+    cmp qword ptr [r12 + 16], TAG_INT
+    jne rt_fail_mismatched
+    cmp qword ptr [r12], TAG_SCOPE
+    jne 2f
+    push r13
+    push r14
+    mov r13, [r12 + 24]
+    mov rax, [r12 + 8]
+    mov r14, [rax + DESC_CODE]
+1:
+    test r13, r13
+    jle 3f
+    call r14
+    dec r13
+    jmp 1b
+3:
+    pop r14
+    pop r13
+2:
+    ret
+
+op_while:
+    NEED 2
+    sub r12, 32
+    mov rcx, [r12 + 16]
+    mov rax, [r12 + 24]
+    mov rdx, [r12]
+    mov r8, [r12 + 8]
+    mov [r12], rcx
+    mov [r12 + 8], rax
+    add r12, 16
+    cmp rdx, TAG_SCOPE
+    jne 2f
+    push r14
+    mov r14, [r8 + DESC_CODE]
+1:
+    NEED 1
+    mov rdi, [r12 - 16]
+    mov rsi, [r12 - 8]
+    call rt_truthy
+    test rax, rax
+    jz 3f
+    call r14
+    jmp 1b
+3:
+    pop r14
+2:
+    ret
+
+op_print:
+    NEED 1
+    mov rdi, [r12 - 16]
+    mov rsi, [r12 - 8]
+    jmp rt_write_display
+
+op_println:
+    call op_print
+    WRITE str_newline
+    ret
+
+op_print_debug:
+    NEED 1
+    mov rdi, [r12 - 16]
+    mov rsi, [r12 - 8]
+    call rt_write_debug
+    WRITE str_element
+    mov rax, r12
+    lea rdx, [rip + value_stack]
+    sub rax, rdx
+    shr rax, 4
+    call rt_write_int
+    WRITE str_element_end
+    ret
+
+op_print_debug_stack:
+    NEED 1
+    WRITE str_list_open
+    push r13
+    lea r13, [rip + value_stack]
+1:
+    cmp r13, r12
+    jae 2f
+    mov rdi, [r13]
+    mov rsi, [r13 + 8]
+    call rt_write_entry
+    add r13, 16
+    jmp 1b
+2:
+    pop r13
+    WRITE str_list_close
+    ret
+
+op_switch:
+    NEED 2
+    movdqu xmm0, [r12 - 32]
+    movdqu xmm1, [r12 - 16]
+    movdqu [r12 - 32], xmm1
+    movdqu [r12 - 16], xmm0
+    ret
+
+op_reverse:
+    lea rax, [rip + value_stack]
+    lea rcx, [r12 - 16]
+1:
+    cmp rax, rcx
+    jae 2f
+    movdqu xmm0, [rax]
+    movdqu xmm1, [rcx]
+    movdqu [rax], xmm1
+    movdqu [rcx], xmm0
+    add rax, 16
+    sub rcx, 16
+    jmp 1b
+2:
+    ret
+
+op_pop:
+    lea rax, [rip + value_stack]
+    cmp r12, rax
+    jbe 1f
+    sub r12, 16
+1:
+    ret
+
+op_copy:
+    NEED 1
+    mov rcx, [r12 - 16]
+    mov rax, [r12 - 8]
+    jmp rt_push
@@ -0,0 +1,86 @@
+use super::*;
+use crate::{runtime, util::host::SharedBuffer};
+
+const SOURCE: &str = "1 2 +\n{ 3 * } 2 for\nprintln";
+
+/// Runs `SOURCE` with the commands as the input, gives what the debugger wrote and the result.
+fn debug(breakpoints: &[u32], commands: &str) -> (String, error::runtime::Result<()>) {
+    let mut stack = Vec::new();
+    crate::ast::fill(
+        SOURCE,
+        &mut stack,
+        &mut 1,
+        &mut 1,
+        &mut std::collections::HashMap::new(),
+    )
+    .unwrap();
+
+    let output = SharedBuffer::default();
+    let debugger = Debugger::new(
+        SOURCE,
+        breakpoints.iter().copied(),
+        io::Cursor::new(commands.to_string()),
+        output.clone(),
+    );
+    let mut context = Context {
+        hook: Some(Box::new(debugger)),
+        host: Box::new(crate::util::host::BufferedHost::default()),
+        ..Default::default()
+    };
+    let result = runtime::run(stack, &mut Vec::new(), &mut context);
+
+    let output = output.take();
+    (output, result)
+}
+
+#[test]
+fn test_debugger_step() {
+    let (output, result) = debug(&[], "s\n\nscopes\nq\n");
+
+    assert!(matches!(result, Err(RuntimeError::Stopped { steps: 9 })));
+    assert_eq!(
+        output,
+        "\
+1:5 `+`
+    1 | 1 2 +
+stack: [Int(1), Int(2)]
+(debug) 2:11 `for`
+    2 | { 3 * } 2 for
+stack: [Int(3), Scope([Value(Int(3)), Operation(Mul)]), Int(2)]
+(debug) 2:5 `*`
+    2 | { 3 * } 2 for
+stack: [Int(3), Int(3)]
+(debug) #0 program
+#1 2:11 | { 3 * } 2 for
+(debug) "
+    );
+}
+
+#[test]
+fn test_debugger_next_skips_scopes() {
+    let (output, result) = debug(&[], "n\nn\nn\n");
+
+    assert!(result.is_ok());
+    assert!(!output.contains("`*`"));
+    assert!(output.ends_with("3:1 `println`\n    3 | println\nstack: [Int(27)]\n(debug) "));
+}
+
+#[test]
+fn test_debugger_breakpoints() {
+    // A breakpoint stops once when its line is entered, also in a scope.
+    let (output, _) = debug(&[2], "c\nc\nd 2\nc\n");
+    let stops: Vec<&str> = output.lines().filter(|line| line.contains('`')).collect();
+
+    assert_eq!(stops, ["1:5 `+`", "(debug) 2:11 `for`", "(debug) 2:5 `*`",]);
+}
+
+#[test]
+fn test_debugger_commands() {
+    let (output, _) = debug(&[], "b\nb 3\nfoo\nc\nstack\n");
+
+    assert!(output.contains("(debug) Expected a line number.\n"));
+    assert!(output.contains("(debug) Breakpoint at line 3.\n"));
+    assert!(output.contains("Unknown command `foo`"));
+    assert!(output.contains("(debug) 3:1 `println`"));
+    assert!(output.contains("[\n    Int(\n        27,\n    ),\n]\n"));
+}
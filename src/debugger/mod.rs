@@ -0,0 +1,225 @@
+/*!
+Watching the program as it runs through `runtime::Hook`s: the interactive debugger of
+`stack_lang debug` and the tracing of `run --trace`.
+*/
+#[cfg(test)]
+mod debugger_test;
+pub mod trace;
+#[cfg(test)]
+mod trace_test;
+
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    runtime::{Context, Hook},
+    util::error::{self, RuntimeError},
+    Location, OperationType, ValueType,
+};
+
+const HELP: &str = "\
+step, s            run until the next operation
+next, n            run until the next operation outside of the scopes it starts
+continue, c        run until a breakpoint
+break, b <LINE>    stop at the first operation of the line
+delete, d <LINE>   remove the breakpoint
+stack              print the values on the stack
+scopes             print the scopes that are running
+quit, q            stop the program
+help, h            print this message
+An empty line repeats the previous command.
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// Stop at the next operation.
+    Step,
+    /// Stop at the next operation in a scope at most this deep.
+    Next(usize),
+    /// Stop only at breakpoints.
+    Continue,
+}
+
+/// Reads commands from the input and writes what it shows to the output.
+pub struct Debugger<R, W> {
+    input: R,
+    output: W,
+    /// Lines of the source, shown next to the locations.
+    source: Vec<String>,
+    breakpoints: BTreeSet<u32>,
+    mode: Mode,
+    /// Line and scope depth of the previous operation, a breakpoint stops when the line is entered.
+    previous: Option<(u32, usize)>,
+    last_command: String,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// The debugger stops before the first operation.
+    pub fn new(
+        source: &str,
+        breakpoints: impl IntoIterator<Item = u32>,
+        input: R,
+        output: W,
+    ) -> Self {
+        Debugger {
+            input,
+            output,
+            source: source.lines().map(str::to_string).collect(),
+            breakpoints: breakpoints.into_iter().collect(),
+            mode: Mode::Step,
+            previous: None,
+            last_command: String::new(),
+        }
+    }
+
+    fn show(
+        &mut self,
+        operation: OperationType,
+        value_stack: &[ValueType],
+        context: &Context,
+    ) -> io::Result<()> {
+        match &context.location {
+            Some(location) => writeln!(
+                self.output,
+                "{location} `{}`\n{:>5} | {}",
+                operation.keyword(),
+                location.line,
+                source_line(&self.source, location)
+            )?,
+            None => writeln!(self.output, "`{}`", operation.keyword())?,
+        }
+
+        writeln!(self.output, "stack: {value_stack:?}")
+    }
+
+    fn show_scopes(&mut self, context: &Context) -> io::Result<()> {
+        for (depth, started) in context.scopes.iter().enumerate() {
+            match started {
+                Some(location) => writeln!(
+                    self.output,
+                    "#{depth} {location} | {}",
+                    source_line(&self.source, location)
+                )?,
+                None => writeln!(self.output, "#{depth} program")?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads commands until one of them resumes the program.
+    fn prompt(&mut self, value_stack: &[ValueType], context: &Context) -> io::Result<Resume> {
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+
+            let mut command = String::new();
+            if self.input.read_line(&mut command)? == 0 {
+                // Without more commands the program runs to the end.
+                writeln!(self.output)?;
+                self.breakpoints.clear();
+                return Ok(Resume::Mode(Mode::Continue));
+            }
+            let command = match command.trim() {
+                "" => self.last_command.clone(),
+                command => command.to_string(),
+            };
+            self.last_command.clone_from(&command);
+
+            let mut words = command.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let line = words.next().map(str::parse::<u32>);
+            match (name, line) {
+                ("step" | "s", _) => return Ok(Resume::Mode(Mode::Step)),
+                ("next" | "n", _) => return Ok(Resume::Mode(Mode::Next(context.scopes.len()))),
+                ("continue" | "c", _) => return Ok(Resume::Mode(Mode::Continue)),
+                ("quit" | "q", _) => return Ok(Resume::Quit),
+                ("break" | "b", Some(Ok(line))) => {
+                    self.breakpoints.insert(line);
+                    writeln!(self.output, "Breakpoint at line {line}.")?;
+                }
+                ("delete" | "d", Some(Ok(line))) => {
+                    if self.breakpoints.remove(&line) {
+                        writeln!(self.output, "Removed the breakpoint at line {line}.")?;
+                    } else {
+                        writeln!(self.output, "There is no breakpoint at line {line}.")?;
+                    }
+                }
+                ("break" | "b" | "delete" | "d", _) => {
+                    writeln!(self.output, "Expected a line number.")?
+                }
+                ("stack", _) => writeln!(self.output, "{value_stack:#?}")?,
+                ("scopes", _) => self.show_scopes(context)?,
+                ("help" | "h", _) => write!(self.output, "{HELP}")?,
+                (unknown, _) => writeln!(
+                    self.output,
+                    "Unknown command `{unknown}`, `help` lists the commands."
+                )?,
+            }
+        }
+    }
+}
+
+fn source_line<'a>(source: &'a [String], location: &Location) -> &'a str {
+    source
+        .get(location.line as usize - 1)
+        .map_or("", |line| line.trim())
+}
+
+enum Resume {
+    Mode(Mode),
+    Quit,
+}
+
+impl<R: BufRead, W: Write> Hook for Debugger<R, W> {
+    fn before_operation(
+        &mut self,
+        operation: OperationType,
+        value_stack: &[ValueType],
+        context: &Context,
+    ) -> error::runtime::Result<()> {
+        let depth = context.scopes.len();
+        let line = context.location.as_ref().map(|location| location.line);
+        let entered_line = line.is_some_and(|line| {
+            self.breakpoints.contains(&line) && self.previous != Some((line, depth))
+        });
+        self.previous = line.map(|line| (line, depth));
+
+        let stop = entered_line
+            || match self.mode {
+                Mode::Step => true,
+                Mode::Next(max_depth) => depth <= max_depth,
+                Mode::Continue => false,
+            };
+        if !stop {
+            return Ok(());
+        }
+
+        let output_failed = |err: io::Error| RuntimeError::OutputFailed {
+            reason: err.to_string(),
+        };
+        self.show(operation, value_stack, context)
+            .map_err(output_failed)?;
+        match self.prompt(value_stack, context).map_err(output_failed)? {
+            Resume::Mode(mode) => {
+                self.mode = mode;
+                Ok(())
+            }
+            Resume::Quit => Err(RuntimeError::Stopped {
+                steps: context.steps,
+            }),
+        }
+    }
+}
+
+impl<R, W> Debug for Debugger<R, W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Debugger")
+            .field("breakpoints", &self.breakpoints)
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
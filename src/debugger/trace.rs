@@ -0,0 +1,274 @@
+/*!
+Tracing of `run --trace`, every operation is written with the stack before and after it.
+*/
+use std::{
+    fmt::{Debug, Write as _},
+    io::Write,
+    ops::RangeInclusive,
+};
+
+use crate::{
+    runtime::{Context, Hook},
+    util::error::{self, RuntimeError},
+    Location, OperationType, ValueType,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    /// A line for every operation, indented by the depth of its scope.
+    #[default]
+    Text,
+    /// A JSON object for the start and the end of every operation, one per line.
+    Json,
+}
+
+/// What is traced and how.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    pub format: Format,
+    /// Only the operations on these lines.
+    pub lines: Option<RangeInclusive<u32>>,
+    /// Only the operations inside of the scopes started on this line.
+    pub scope: Option<u32>,
+}
+
+/// Operation that didn't end yet.
+struct Call {
+    depth: usize,
+    step: u64,
+    location: Option<Location>,
+    operation: OperationType,
+    before: String,
+    traced: bool,
+    /// Whether the operation was already written because it ran scopes, which are written in between.
+    written: bool,
+}
+
+pub struct Tracer<W> {
+    output: W,
+    options: Options,
+    calls: Vec<Call>,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(output: W, options: Options) -> Self {
+        Tracer {
+            output,
+            options,
+            calls: Vec::new(),
+        }
+    }
+
+    fn traced(&self, context: &Context) -> bool {
+        let line = context.location.as_ref().map(|location| location.line);
+        let in_lines = match &self.options.lines {
+            Some(lines) => line.is_some_and(|line| lines.contains(&line)),
+            None => true,
+        };
+        let in_scope = match self.options.scope {
+            Some(scope) => context.scopes.iter().any(|started| {
+                started
+                    .as_ref()
+                    .is_some_and(|location| location.line == scope)
+            }),
+            None => true,
+        };
+
+        in_lines && in_scope
+    }
+
+    /// Writes the text of the call, without the stack after it if it didn't end yet.
+    fn write_text(&mut self, call: &Call, after: Option<&[ValueType]>) -> std::io::Result<()> {
+        let indent = "  ".repeat(call.depth.saturating_sub(1));
+        let location = call
+            .location
+            .as_ref()
+            .map_or("-".to_string(), Location::to_string);
+        let operation = call.operation.keyword();
+
+        match (after, call.written) {
+            (Some(after), false) => writeln!(
+                self.output,
+                "{indent}{location} {operation} {} -> {after:?}",
+                call.before
+            ),
+            (Some(after), true) => {
+                writeln!(self.output, "{indent}{location} {operation} -> {after:?}")
+            }
+            (None, _) => writeln!(
+                self.output,
+                "{indent}{location} {operation} {}",
+                call.before
+            ),
+        }
+    }
+
+    fn write_json(
+        &mut self,
+        event: &str,
+        call: &Call,
+        value_stack: &[ValueType],
+    ) -> std::io::Result<()> {
+        let mut line = format!("{{\"event\":\"{event}\",\"step\":{}", call.step);
+        if let Some(location) = &call.location {
+            write!(
+                line,
+                ",\"line\":{},\"column\":{}",
+                location.line, location.column
+            )
+            .unwrap();
+            if let Some(origin) = &location.origin {
+                write!(line, ",\"origin\":{}", json_string(origin)).unwrap();
+            }
+        }
+        write!(
+            line,
+            ",\"depth\":{},\"operation\":{},\"stack\":[",
+            call.depth,
+            json_string(call.operation.keyword())
+        )
+        .unwrap();
+        for (index, value) in value_stack.iter().enumerate() {
+            if index > 0 {
+                line.push(',');
+            }
+            line.push_str(&json_value(value));
+        }
+        line.push_str("]}");
+
+        writeln!(self.output, "{line}")
+    }
+
+    /**
+    Calls deeper than the given depth failed and were caught by `try`,
+    they are written without the stack after them.
+    */
+    fn drop_failed_calls(&mut self, depth: usize) -> std::io::Result<()> {
+        while self.calls.last().is_some_and(|call| call.depth > depth) {
+            let call = self.calls.pop().unwrap();
+            if call.traced && !call.written && self.options.format == Format::Text {
+                self.write_text(&call, None)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Hook for Tracer<W> {
+    fn before_operation(
+        &mut self,
+        operation: OperationType,
+        value_stack: &[ValueType],
+        context: &Context,
+    ) -> error::runtime::Result<()> {
+        let depth = context.scopes.len();
+        // The previous operation at the same depth ended, if it's still here it failed.
+        self.drop_failed_calls(depth - 1).map_err(output_failed)?;
+
+        let call = Call {
+            depth,
+            step: context.steps,
+            location: context.location.clone(),
+            operation,
+            before: match self.options.format {
+                Format::Text => format!("{value_stack:?}"),
+                Format::Json => String::new(),
+            },
+            traced: self.traced(context),
+            written: false,
+        };
+
+        let result = match self.options.format {
+            // The operation that runs this one is written before it.
+            Format::Text => match self.calls.pop() {
+                Some(mut outer) => {
+                    let result = if outer.traced && !outer.written {
+                        self.write_text(&outer, None)
+                    } else {
+                        Ok(())
+                    };
+                    outer.written = true;
+                    self.calls.push(outer);
+
+                    result
+                }
+                None => Ok(()),
+            },
+            Format::Json if call.traced => self.write_json("before", &call, value_stack),
+            Format::Json => Ok(()),
+        };
+        self.calls.push(call);
+
+        result.map_err(output_failed)
+    }
+
+    fn after_operation(
+        &mut self,
+        _operation: OperationType,
+        value_stack: &[ValueType],
+        context: &Context,
+    ) -> error::runtime::Result<()> {
+        self.drop_failed_calls(context.scopes.len())
+            .map_err(output_failed)?;
+        let Some(call) = self.calls.pop() else {
+            return Ok(());
+        };
+        if !call.traced {
+            return Ok(());
+        }
+
+        match self.options.format {
+            Format::Text => self.write_text(&call, Some(value_stack)),
+            Format::Json => self.write_json("after", &call, value_stack),
+        }
+        .map_err(output_failed)
+    }
+}
+
+impl<W> Debug for Tracer<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tracer")
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+fn output_failed(err: std::io::Error) -> RuntimeError {
+    RuntimeError::OutputFailed {
+        reason: err.to_string(),
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+
+    json.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            ch if ch.is_control() => write!(json, "\\u{:04x}", ch as u32).unwrap(),
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+
+    json
+}
+
+/// The type and the value, Scopes are written like `Debug` and Floats JSON can't hold as `null`.
+fn json_value(value: &ValueType) -> String {
+    let (kind, value) = match value {
+        ValueType::Int(int) => ("Int", int.to_string()),
+        ValueType::Float(float) if float.is_finite() => ("Float", format!("{float:?}")),
+        ValueType::Float(_) => ("Float", "null".to_string()),
+        ValueType::Text(text) => ("Text", json_string(text)),
+        ValueType::Bool(condition) => ("Bool", condition.to_string()),
+        ValueType::Scope(_) => ("Scope", json_string(&format!("{value:?}"))),
+    };
+
+    format!("{{\"type\":\"{kind}\",\"value\":{value}}}")
+}
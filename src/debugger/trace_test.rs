@@ -0,0 +1,98 @@
+use super::trace::*;
+use crate::{
+    runtime::{self, Context},
+    util::host::{BufferedHost, SharedBuffer},
+};
+
+fn trace(source: &str, options: Options) -> String {
+    let mut stack = Vec::new();
+    crate::ast::fill(
+        source,
+        &mut stack,
+        &mut 1,
+        &mut 1,
+        &mut std::collections::HashMap::new(),
+    )
+    .unwrap();
+
+    let output = SharedBuffer::default();
+    let mut context = Context {
+        hook: Some(Box::new(Tracer::new(output.clone(), options))),
+        host: Box::new(BufferedHost::default()),
+        ..Default::default()
+    };
+    let _ = runtime::run(stack, &mut Vec::new(), &mut context);
+
+    output.take()
+}
+
+#[test]
+fn test_trace_text() {
+    assert_eq!(
+        trace("1 2 +\n{ 3 * } 2 for", Options::default()),
+        "\
+1:5 + [Int(1), Int(2)] -> [Int(3)]
+2:11 for [Int(3), Scope([Value(Int(3)), Operation(Mul)]), Int(2)]
+  2:5 * [Int(3), Int(3)] -> [Int(9)]
+  2:5 * [Int(9), Int(3)] -> [Int(27)]
+2:11 for -> [Int(27)]
+"
+    );
+}
+
+#[test]
+fn test_trace_failed_operation() {
+    // The operation that failed has no stack after it, `try` goes on after the failed scope.
+    assert_eq!(
+        trace("{ 1 \"a\" + } { pop pop } try 5", Options::default()),
+        "\
+1:25 try [Scope([Value(Int(1)), Value(Text(\"a\")), Operation(Add)]), Scope([Operation(Pop), Operation(Pop)])]
+  1:9 + [Int(1), Text(\"a\")]
+  1:15 pop [Text(\"MismatchedTypes\"), Text(\"Expected Int but got Text(\\\"a\\\").\")] -> [Text(\"MismatchedTypes\")]
+  1:19 pop [Text(\"MismatchedTypes\")] -> []
+1:25 try -> []
+"
+    );
+}
+
+#[test]
+fn test_trace_filters() {
+    let source = "1 2 +\n{ 3 * } 2 for\n{ 4 + } true if";
+
+    let lines = Options {
+        lines: Some(3..=3),
+        ..Default::default()
+    };
+    assert_eq!(
+        trace(source, lines),
+        "\
+3:14 if [Int(27), Scope([Value(Int(4)), Operation(Add)]), Bool(true)]
+  3:5 + [Int(27), Int(4)] -> [Int(31)]
+3:14 if -> [Int(31)]
+"
+    );
+
+    let scope = Options {
+        scope: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(
+        trace(source, scope),
+        "  2:5 * [Int(3), Int(3)] -> [Int(9)]\n  2:5 * [Int(9), Int(3)] -> [Int(27)]\n"
+    );
+}
+
+#[test]
+fn test_trace_json() {
+    let json = Options {
+        format: Format::Json,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        trace("\"a\tb\" { 1.5 } true if", json),
+        r#"{"event":"before","step":4,"line":1,"column":20,"depth":1,"operation":"if","stack":[{"type":"Text","value":"a\tb"},{"type":"Scope","value":"Scope([Value(Float(1.5))])"},{"type":"Bool","value":true}]}
+{"event":"after","step":4,"line":1,"column":20,"depth":1,"operation":"if","stack":[{"type":"Text","value":"a\tb"},{"type":"Float","value":1.5}]}
+"#
+    );
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::{ast, Location, OperationType};
+
+fn parse(src: &str) -> Vec<Stack> {
+    let mut stack = Vec::new();
+
+    ast::fill(src, &mut stack, &mut 1, &mut 1, &mut HashMap::new()).unwrap();
+    ast::strip_locations(&mut stack);
+
+    stack
+}
+
+#[test]
+fn test_listing() {
+    let stack = vec![
+        Stack::Value(ValueType::Int(2)),
+        Stack::Value(ValueType::Scope(vec![Stack::Operation(OperationType::Mul)])),
+        Stack::Value(ValueType::Bool(true)),
+        Stack::Operation(OperationType::If),
+    ];
+
+    assert_eq!(
+        listing(&stack),
+        "index depth  instruction\n\
+        \x20   0     0  value     Int(2)\n\
+        \x20   1     0  value     Scope (1 element)\n\
+        \x20   2     1    operation *\n\
+        \x20   3     0  value     Bool(true)\n\
+        \x20   4     0  operation if\n"
+    );
+}
+
+#[test]
+fn test_listing_locations() {
+    let stack = vec![
+        Stack::Value(ValueType::Int(2)),
+        Stack::Location(Location {
+            line: 3,
+            column: 5,
+            origin: Some("double".to_string()),
+        }),
+        Stack::Operation(OperationType::Mul),
+    ];
+
+    assert_eq!(
+        listing(&stack),
+        "index depth  instruction\n\
+        \x20   0     0  value     Int(2)\n\
+        \x20   1     0  operation *  ; 3:5 in `double`\n"
+    );
+}
+
+#[test]
+fn test_source() {
+    let stack = parse("2 3.5 -1.0 \"text\" {copy *} true if println");
+
+    assert_eq!(
+        source(&stack),
+        "2 3.5 -1.0 \"text\" {\n\tcopy\n\t*\n} true if\nprintln\n"
+    );
+}
+
+#[test]
+fn test_source_locals() {
+    let stack = parse("1 -> x { x 2 * -> x } true if x");
+
+    assert_eq!(
+        source(&stack),
+        "1 -> x\n{\n\tx\n\t2 *\n\t-> x\n} true if\nx\n"
+    );
+    assert_eq!(parse(&source(&stack)), stack);
+}
+
+#[test]
+fn test_source_round_trip_examples() {
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "sl") {
+            continue;
+        }
+
+        let src = std::fs::read_to_string(path).unwrap();
+        let stack = parse(&src);
+
+        assert_eq!(parse(&source(&stack)), stack);
+    }
+}
+
+#[test]
+fn test_source_special_floats() {
+    let stack = vec![
+        Stack::Value(ValueType::Float(f64::INFINITY)),
+        Stack::Value(ValueType::Float(1e21)),
+    ];
+
+    assert_eq!(
+        parse(&source(&stack)),
+        vec![
+            Stack::Value(ValueType::Float(1.0)),
+            Stack::Value(ValueType::Float(0.0)),
+            Stack::Operation(OperationType::Div),
+            Stack::Value(ValueType::Float(1e21)),
+        ]
+    );
+}
@@ -0,0 +1,163 @@
+#[cfg(test)]
+mod disasm_test;
+
+use crate::{OperationType, Stack, ValueType};
+use std::fmt::Write;
+
+/**
+Gives a readable listing of the program.
+
+Every element is numbered in the order it appears in the program and indented by the depth of the scope containing it.
+Elements with a known source location are followed by it.
+
+Example:
+```text
+index depth  instruction
+    0     0  value     Int(2)
+    1     0  value     Scope (1 element)
+    2     1    operation *
+```
+*/
+pub fn listing(stack: &[Stack]) -> String {
+    let mut out = String::from("index depth  instruction\n");
+    let mut index = 0;
+
+    write_listing(stack, 0, &mut index, &mut out);
+
+    out
+}
+
+fn write_listing(stack: &[Stack], depth: usize, index: &mut usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let mut location = None;
+
+    for element in stack {
+        let instruction = match element {
+            Stack::Location(next) => {
+                location = Some(next);
+                continue;
+            }
+            Stack::Operation(operation) => format!("operation {}", operation.keyword()),
+            Stack::Value(ValueType::Scope(scope)) => format!(
+                "value     Scope ({} element{})",
+                scope.len(),
+                if scope.len() == 1 { "" } else { "s" }
+            ),
+            Stack::Value(value) => format!("value     {value:?}"),
+            Stack::Effect(effect) => format!("effect    {} {effect}", effect.name),
+            Stack::EffectEnd(effect) => format!("end       {}", effect.name),
+            Stack::LocalFrame => "locals".to_string(),
+            Stack::LocalFrameEnd => "end       locals".to_string(),
+        };
+
+        write!(out, "{index:>5} {depth:>5}  {indent}{instruction}").unwrap();
+        if let Some(location) = location.take() {
+            write!(out, "  ; {location}").unwrap();
+        }
+        out.push('\n');
+        *index += 1;
+
+        if let Stack::Value(ValueType::Scope(scope)) = element {
+            write_listing(scope, depth + 1, index, out);
+        }
+    }
+}
+
+/**
+Turns the program back into source code that parses into the same program.
+
+Macros and constants are already expanded in binaries so they are printed expanded,
+without the stack effects declared for them.
+*/
+pub fn source(stack: &[Stack]) -> String {
+    let mut out = String::new();
+
+    write_source(stack, 0, &mut out);
+
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+
+    out
+}
+
+fn write_source(stack: &[Stack], depth: usize, out: &mut String) {
+    let mut line_start = true;
+    let mut elements = stack.iter().peekable();
+
+    while let Some(element) = elements.next() {
+        if let Stack::Location(_)
+        | Stack::Effect(_)
+        | Stack::EffectEnd(_)
+        | Stack::LocalFrame
+        | Stack::LocalFrameEnd = element
+        {
+            continue;
+        }
+
+        if line_start {
+            out.push_str(&"\t".repeat(depth));
+        } else {
+            out.push(' ');
+        }
+        line_start = false;
+
+        match (element, elements.peek()) {
+            // Locals are written the way they are bound and read, they have no keywords.
+            (
+                Stack::Value(ValueType::Text(name)),
+                Some(Stack::Operation(
+                    operation @ (OperationType::LocalSet | OperationType::LocalGet),
+                )),
+            ) => {
+                if *operation == OperationType::LocalSet {
+                    out.push_str("-> ");
+                }
+                out.push_str(name);
+                out.push('\n');
+                line_start = true;
+                elements.next();
+            }
+            (Stack::Operation(operation), _) => {
+                out.push_str(operation.keyword());
+                out.push('\n');
+                line_start = true;
+            }
+            (Stack::Value(ValueType::Scope(scope)), _) => {
+                out.push_str("{\n");
+                write_source(scope, depth + 1, out);
+                out.push_str(&"\t".repeat(depth));
+                out.push('}');
+            }
+            (Stack::Value(value), _) => out.push_str(&value_source(value)),
+            (
+                Stack::Location(_)
+                | Stack::Effect(_)
+                | Stack::EffectEnd(_)
+                | Stack::LocalFrame
+                | Stack::LocalFrameEnd,
+                _,
+            ) => unreachable!(),
+        }
+    }
+
+    if !line_start {
+        out.push('\n');
+    }
+}
+
+fn value_source(value: &ValueType) -> String {
+    match value {
+        ValueType::Int(int) => int.to_string(),
+        // Display never uses the exponent notation, which the parser doesn't support.
+        ValueType::Float(float) if float.is_nan() => "0.0 0.0 /".to_string(),
+        ValueType::Float(float) if float.is_infinite() => {
+            format!("{}1.0 0.0 /", if *float < 0.0 { "-" } else { "" })
+        }
+        ValueType::Float(float) if float.fract() == 0.0 => format!("{float}.0"),
+        ValueType::Float(float) => float.to_string(),
+        ValueType::Text(text) => format!("\"{text}\""),
+        ValueType::Bool(condition) => condition.to_string(),
+        ValueType::Scope(_) => unreachable!("Scopes are written by `write_source`."),
+    }
+}
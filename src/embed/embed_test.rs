@@ -0,0 +1,82 @@
+use super::*;
+
+#[test]
+fn test_eval() {
+    assert_eq!(
+        eval("2 3 + println {\"big\" println} true if"),
+        Evaluation {
+            stdout: "5\nbig\n".to_string(),
+            stack: vec![ValueType::Int(5), ValueType::Text("big".to_string())],
+            error: None,
+        }
+    );
+}
+
+#[test]
+fn test_eval_parsing_error() {
+    let evaluation = eval("1 2\n3 unknown_word");
+
+    assert_eq!(evaluation.stdout, "");
+    assert!(evaluation
+        .error
+        .unwrap()
+        .starts_with("Parsing Error at 2:3\n"));
+}
+
+#[test]
+fn test_eval_runtime_error() {
+    let evaluation = eval("1 println 1 \"a\" +");
+
+    assert_eq!(evaluation.stdout, "1\n");
+    assert_eq!(evaluation.stack, vec![ValueType::Int(1)]);
+    assert_eq!(
+        evaluation.error.unwrap(),
+        "Runtime Error at 1:17\n\tExpected Int but got Text(\"a\")."
+    );
+}
+
+#[test]
+fn test_eval_denies_file_system() {
+    let evaluation = eval("\"Cargo.toml\" file_exists");
+
+    assert!(evaluation.error.unwrap().contains("is not allowed"));
+}
+
+#[test]
+fn test_eval_imports_only_the_prelude() {
+    assert_eq!(
+        eval("\"std/math\" import 3 square").stack,
+        vec![ValueType::Int(9)]
+    );
+
+    let evaluation = eval("\"Cargo.toml\" import");
+    assert!(evaluation
+        .error
+        .unwrap()
+        .contains("Only the prelude modules can be imported here."));
+}
+
+#[test]
+fn test_eval_with_limits() {
+    let limits = runtime::Limits {
+        max_steps: Some(1000),
+        ..Default::default()
+    };
+    let evaluation = eval_with_limits("{ } true while", limits);
+
+    assert_eq!(
+        evaluation.error.unwrap(),
+        "Runtime Error at 1:10\n\tThe program didn't finish in 1000 steps."
+    );
+
+    let limits = runtime::Limits {
+        timeout: Some(std::time::Duration::from_millis(10)),
+        ..Default::default()
+    };
+    let evaluation = eval_with_limits("{ } true while", limits);
+
+    assert!(evaluation
+        .error
+        .unwrap()
+        .contains("The program didn't finish in 10ms"));
+}
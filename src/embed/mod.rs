@@ -0,0 +1,169 @@
+/*!
+Entry point for embedding the interpreter, e.g. as a `wasm32-unknown-unknown` module.
+*/
+#[cfg(test)]
+mod embed_test;
+
+use std::collections::HashMap;
+
+use crate::{ast, runtime, util::host::BufferedHost, ValueType};
+
+/// Result of evaluating a program with `eval`.
+#[derive(Debug, PartialEq)]
+pub struct Evaluation {
+    /// Everything the program printed.
+    pub stdout: String,
+    /// Values left on the stack, also when the program failed.
+    pub stack: Vec<ValueType>,
+    /// Parsing or runtime error that stopped the program.
+    pub error: Option<String>,
+}
+
+/**
+Parses and runs the source without touching the process or the file system.
+Only the prelude modules can be imported, importing files fails.
+
+Output is collected instead of printed, so the interpreter doesn't need anything from the environment.
+*/
+pub fn eval(source: &str) -> Evaluation {
+    eval_with_limits(source, runtime::Limits::default())
+}
+
+/**
+Same as `eval`, the program is stopped with an error when it reaches one of the limits.
+
+`wasm32` has no clock, so a timeout is rejected there without running the program.
+*/
+pub fn eval_with_limits(source: &str, limits: runtime::Limits) -> Evaluation {
+    let mut evaluation = Evaluation {
+        stdout: String::new(),
+        stack: Vec::new(),
+        error: None,
+    };
+
+    #[cfg(target_arch = "wasm32")]
+    let host = match limits.timeout {
+        Some(_) => {
+            evaluation.error = Some("Timeouts aren't supported on this target.".to_string());
+            return evaluation;
+        }
+        None => BufferedHost::default(),
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let host = BufferedHost::with_clock();
+
+    let mut stack = Vec::new();
+    let mut line_height = 1;
+    let mut line_width = 1;
+    if let Err(err) = ast::fill_with_imports(
+        source,
+        &mut stack,
+        &mut line_height,
+        &mut line_width,
+        &mut HashMap::new(),
+        &mut ast::Imports::prelude_only(),
+    ) {
+        evaluation.error = Some(format!(
+            "Parsing Error at {line_height}:{line_width}\n{err}"
+        ));
+        return evaluation;
+    }
+
+    let mut context = runtime::Context {
+        limits,
+        ..runtime::Context::new(Box::new(host.clone()))
+    };
+    if let Err(err) = runtime::run(stack, &mut evaluation.stack, &mut context) {
+        evaluation.error = Some(match context.location {
+            Some(location) => format!("Runtime Error at {location}\n{err}"),
+            None => format!("Runtime Error\n{err}"),
+        });
+    }
+
+    evaluation.stdout = host.stdout.take();
+
+    evaluation
+}
+
+/**
+Functions exported from the `wasm32` module, they only need the host to read and write the module's memory.
+
+```text
+source = stack_lang_alloc(len)           // then write the UTF-8 source there
+result = eval(source, len)               // 6 little endian u32: pointer and length of
+                                         // stdout, stack and error, error is empty on success
+stack_lang_free_result(result)
+stack_lang_free(source, len)
+```
+*/
+#[cfg(target_arch = "wasm32")]
+mod abi {
+    use super::eval as evaluate;
+
+    #[repr(C)]
+    pub struct Bytes {
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    #[repr(C)]
+    pub struct EvalResult {
+        stdout: Bytes,
+        stack: Bytes,
+        error: Bytes,
+    }
+
+    fn leak(bytes: Vec<u8>) -> Bytes {
+        let bytes = bytes.into_boxed_slice();
+        let len = bytes.len();
+
+        Bytes {
+            ptr: Box::into_raw(bytes) as *mut u8,
+            len,
+        }
+    }
+
+    /// # Safety
+    /// The bytes have to come from `leak`.
+    unsafe fn free(bytes: Bytes) {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            bytes.ptr, bytes.len,
+        )));
+    }
+
+    #[no_mangle]
+    pub extern "C" fn stack_lang_alloc(len: usize) -> *mut u8 {
+        leak(vec![0; len]).ptr
+    }
+
+    /// # Safety
+    /// Pointer and length have to come from `stack_lang_alloc`.
+    #[no_mangle]
+    pub unsafe extern "C" fn stack_lang_free(ptr: *mut u8, len: usize) {
+        free(Bytes { ptr, len });
+    }
+
+    /// # Safety
+    /// The source has to be valid UTF-8 allocated by `stack_lang_alloc`.
+    #[no_mangle]
+    pub unsafe extern "C" fn eval(ptr: *const u8, len: usize) -> *mut EvalResult {
+        let source = std::str::from_utf8_unchecked(std::slice::from_raw_parts(ptr, len));
+        let evaluation = evaluate(source);
+
+        Box::into_raw(Box::new(EvalResult {
+            stdout: leak(evaluation.stdout.into_bytes()),
+            stack: leak(format!("{:?}", evaluation.stack).into_bytes()),
+            error: leak(evaluation.error.unwrap_or_default().into_bytes()),
+        }))
+    }
+
+    /// # Safety
+    /// The result has to come from `eval`.
+    #[no_mangle]
+    pub unsafe extern "C" fn stack_lang_free_result(result: *mut EvalResult) {
+        let result = Box::from_raw(result);
+        free(result.stdout);
+        free(result.stack);
+        free(result.error);
+    }
+}
@@ -0,0 +1,76 @@
+#![allow(clippy::unit_arg)]
+pub mod ast;
+pub mod check;
+pub mod codegen;
+pub mod debugger;
+pub mod disasm;
+pub mod embed;
+pub mod prelude;
+pub mod runtime;
+pub mod util;
+
+pub use ast::{OperationType, ValueType};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Stack {
+    Value(ValueType),
+    Operation(OperationType),
+    /// Position in the source of the element that follows. It is skipped during execution.
+    Location(Location),
+    /// Start of an expanded macro with a declared stack effect, the stack has to hold its inputs.
+    Effect(Effect),
+    /// End of the expanded macro, the stack depth has to match the declared effect.
+    EffectEnd(Effect),
+    /// Start of an expanded macro that binds locals, `->` in it doesn't change the caller's locals.
+    LocalFrame,
+    /// End of the expanded macro, the locals it bound are dropped.
+    LocalFrameEnd,
+}
+
+#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Location {
+    pub line: u32,
+    pub column: u32,
+    /// Name of the macro or constant the element was expanded from.
+    pub origin: Option<String>,
+}
+
+/**
+Stack effect declared for a macro, like `( a b -- sum )`.
+
+Names of the values only document them, the number of values is checked.
+*/
+#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Effect {
+    /// Name of the macro, empty until the macro is registered.
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+impl std::fmt::Display for Effect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for input in &self.inputs {
+            write!(f, " {input}")?;
+        }
+        write!(f, " --")?;
+        for output in &self.outputs {
+            write!(f, " {output}")?;
+        }
+        write!(f, " )")
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)?;
+
+        if let Some(origin) = &self.origin {
+            write!(f, " in `{origin}`")?;
+        }
+
+        Ok(())
+    }
+}
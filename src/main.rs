@@ -1,105 +1,483 @@
-#![allow(clippy::unit_arg)]
-mod ast;
-mod runtime;
-mod util;
-
-use ast::*;
-use std::{collections::HashMap, path::PathBuf};
-use util::{cli::ExecutionMode::*, *};
+use stack_lang_compiler::{
+    ast, check, codegen, debugger, disasm, runtime, time_phase,
+    util::{
+        cli::ExecutionMode::*,
+        timing::{Phase, Timings},
+        *,
+    },
+    Location, Stack, ValueType,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 const DEFAULT_STACK_SIZE: usize = 128;
 
-#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
-pub enum Stack {
-    Value(ValueType),
-    Operation(OperationType),
-}
-
 fn main() {
-    let execution_mode = cli::get_execution_mode();
+    let embedded = std::env::current_exe()
+        .ok()
+        .and_then(|executable| file::extract_embedded(&executable));
+    let execution_mode = match embedded {
+        Some(payload) => cli::get_standalone_mode(payload),
+        None => cli::get_execution_mode(),
+    };
 
-    let _leftover_stack = match execution_mode {
-        Run { path } => {
+    match execution_mode {
+        Run {
+            path,
+            fs_access,
+            options,
+        } => {
             let mut stack = Vec::with_capacity(DEFAULT_STACK_SIZE);
+            let mut timings = Timings::default();
+            let host = host::StdHost::new(options.flush);
 
-            let src = file::extract_text(&path);
-            parse(&src, &mut stack, Some(path));
+            let src = time_phase!(timings, host, Phase::Read, file::extract_text(&path));
+            time_phase!(
+                timings,
+                host,
+                Phase::Parse,
+                parse(&src, &mut stack, Some(path.clone()))
+            );
 
-            execute(stack)
+            let context = runtime::Context {
+                fs_access,
+                host: Box::new(host),
+                limits: options.limits,
+                hook: tracer(&options),
+                ..Default::default()
+            };
+            execute(
+                stack,
+                context,
+                Some(path.display().to_string()),
+                &options,
+                timings,
+            );
         }
-        RunBinary { path } => {
-            let src = file::extract_bin(&path);
-            let bin: file::Binary = log_debug_time!(
-                postcard::from_bytes(&src).expect("Binary file format is not valid."),
-                "Building from binary."
+        RunBinary {
+            path,
+            fs_access,
+            options,
+        } => {
+            let mut timings = Timings::default();
+            let host = host::StdHost::new(options.flush);
+
+            let src = time_phase!(timings, host, Phase::Read, read_binary(&path));
+            let bin = time_phase!(timings, host, Phase::Deserialize, decode_binary(&src));
+
+            let context = runtime::Context {
+                fs_access,
+                host: Box::new(host),
+                limits: options.limits,
+                hook: tracer(&options),
+                ..Default::default()
+            };
+            execute(
+                bin.stack,
+                context,
+                bin.debug.map(|debug| debug.source_file),
+                &options,
+                timings,
             );
+        }
+        RunEmbedded {
+            payload,
+            fs_access,
+            options,
+        } => {
+            let mut timings = Timings::default();
+            let host = host::StdHost::new(options.flush);
+
+            let bin = time_phase!(timings, host, Phase::Deserialize, decode_binary(&payload));
 
-            execute(bin.stack)
+            let context = runtime::Context {
+                fs_access,
+                host: Box::new(host),
+                limits: options.limits,
+                hook: tracer(&options),
+                ..Default::default()
+            };
+            execute(
+                bin.stack,
+                context,
+                bin.debug.map(|debug| debug.source_file),
+                &options,
+                timings,
+            );
         }
         Build {
             input_file,
             output_file,
+            strip,
+            standalone,
+            target,
         } => {
             let mut stack = Vec::with_capacity(DEFAULT_STACK_SIZE);
 
             let src = file::extract_text(&input_file);
-            parse(&src, &mut stack, Some(input_file));
+            parse(&src, &mut stack, Some(input_file.clone()));
 
-            let bin = file::Binary { stack };
-            let bytes =
-                postcard::to_allocvec(&bin).expect("Couldn't convert stack to binary file.");
+            let debug = if strip {
+                ast::strip_locations(&mut stack);
+                None
+            } else {
+                Some(file::DebugInfo {
+                    source_file: input_file.display().to_string(),
+                })
+            };
 
-            file::write_bin(
-                bytes,
-                &output_file.unwrap_or_else(|| PathBuf::from("a.out")),
-            );
+            match target {
+                cli::Target::Binary => {
+                    let bin = file::Binary { stack, debug };
+                    let bytes = bin
+                        .to_bytes()
+                        .expect("Couldn't convert stack to binary file.");
 
-            Vec::new()
+                    let output_file = output_file.unwrap_or_else(|| PathBuf::from("a.out"));
+                    if standalone {
+                        let interpreter =
+                            std::env::current_exe().expect("Could not locate the interpreter.");
+                        file::write_standalone(&interpreter, bytes, &output_file)
+                            .unwrap_or_else(|err| file_error("write", &output_file, err));
+                    } else {
+                        write_output(bytes, &output_file);
+                    }
+                }
+                cli::Target::X86_64Asm => {
+                    let assembly =
+                        codegen::x86_64::compile(&stack).unwrap_or_else(|err| build_error(err));
+
+                    write_output(
+                        assembly.into_bytes(),
+                        &output_file.unwrap_or_else(|| PathBuf::from("a.s")),
+                    );
+                }
+                cli::Target::C => {
+                    let source = codegen::c::compile(&stack).unwrap_or_else(|err| build_error(err));
+
+                    write_output(
+                        source.into_bytes(),
+                        &output_file.unwrap_or_else(|| PathBuf::from("a.c")),
+                    );
+                }
+                cli::Target::Wasm => {
+                    let module = codegen::wasm::compile_binary(&stack)
+                        .unwrap_or_else(|err| build_error(err));
+
+                    write_output(
+                        module,
+                        &output_file.unwrap_or_else(|| PathBuf::from("a.wasm")),
+                    );
+                }
+                cli::Target::Wat => {
+                    let module =
+                        codegen::wasm::compile(&stack).unwrap_or_else(|err| build_error(err));
+
+                    write_output(
+                        module.into_bytes(),
+                        &output_file.unwrap_or_else(|| PathBuf::from("a.wat")),
+                    );
+                }
+            }
         }
-    };
+        Disassemble { path, as_source } => {
+            let bin = load_binary(&path);
+
+            if let Some(debug) = &bin.debug {
+                println!("; source file: {}", debug.source_file);
+            }
+            if as_source {
+                print!("{}", disasm::source(&bin.stack));
+            } else {
+                print!("{}", disasm::listing(&bin.stack));
+            }
+        }
+        Check { path } => {
+            let mut stack = Vec::with_capacity(DEFAULT_STACK_SIZE);
+
+            let src = file::extract_text(&path);
+            parse(&src, &mut stack, Some(path.clone()));
+
+            let diagnostics = check::check(&stack);
+            if diagnostics.is_empty() {
+                println!("No problems found in {}.", path.display());
+                return;
+            }
+
+            const YELLOW: &str = "\x1b[93m";
+            const UNDERLINE: &str = "\x1b[4m";
+            const BOLD: &str = "\x1b[1m";
+            const RESET_FORMATTING: &str = "\x1b[0m";
+
+            for diagnostic in &diagnostics {
+                match &diagnostic.location {
+                    Some(location) => println!(
+                        "{YELLOW}Check Error at {UNDERLINE}{BOLD}{}:{location}{RESET_FORMATTING}\n{}",
+                        path.display(),
+                        diagnostic.error
+                    ),
+                    None => println!("{YELLOW}Check Error{RESET_FORMATTING}\n{}", diagnostic.error),
+                }
+            }
+            std::process::exit(1);
+        }
+        Test {
+            paths,
+            fs_access,
+            limits,
+        } => {
+            const GREEN: &str = "\x1b[92m";
+            const RED: &str = "\x1b[91m";
+            const RESET_FORMATTING: &str = "\x1b[0m";
+
+            let tests = file::find_tests(&paths).unwrap_or_else(|err| {
+                println!("{RED}Could not search for tests{RESET_FORMATTING}\n\t{err}");
+                std::process::exit(1);
+            });
+
+            let mut passed = 0;
+            let mut failed = 0;
+            for path in tests {
+                let source_file = path.display().to_string();
+
+                let mut stack = Vec::with_capacity(DEFAULT_STACK_SIZE);
+                let parsed = std::fs::read_to_string(&path)
+                    .map_err(|err| {
+                        format!("{RED}Could not read {source_file}{RESET_FORMATTING}\n\t{err}")
+                    })
+                    .and_then(|src| parse_source(&src, &mut stack, Some(path.clone())));
+                if let Err(message) = parsed {
+                    println!("{message}");
+                    failed += 1;
+                    continue;
+                }
+
+                let mut context = runtime::Context {
+                    fs_access: fs_access.clone(),
+                    limits,
+                    ..Default::default()
+                };
+                let outcomes = match runtime::testing::run_tests(stack, &mut context) {
+                    Ok(outcomes) => outcomes,
+                    Err(err) => {
+                        println!(
+                            "{}",
+                            runtime_error(&err, Some(&source_file), context.location.as_ref())
+                        );
+                        failed += 1;
+                        continue;
+                    }
+                };
+
+                for outcome in outcomes {
+                    match &outcome.failure {
+                        None => {
+                            println!(
+                                "test {source_file}: {} ... {GREEN}ok{RESET_FORMATTING}",
+                                outcome.name
+                            );
+                            passed += 1;
+                        }
+                        Some((err, location)) => {
+                            println!(
+                                "test {source_file}: {} ... {RED}FAILED{RESET_FORMATTING}",
+                                outcome.name
+                            );
+                            println!(
+                                "{}",
+                                runtime_error(err, Some(&source_file), location.as_ref())
+                            );
+                            failed += 1;
+                        }
+                    }
+                }
+            }
+
+            println!("\n{passed} passed; {failed} failed");
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Debug {
+            path,
+            fs_access,
+            breakpoints,
+        } => {
+            let mut stack = Vec::with_capacity(DEFAULT_STACK_SIZE);
+
+            let src = file::extract_text(&path);
+            parse(&src, &mut stack, Some(path.clone()));
 
-    #[cfg(debug_assertions)]
-    println!("{:?}", _leftover_stack);
+            let debugger = debugger::Debugger::new(
+                &src,
+                breakpoints,
+                std::io::stdin().lock(),
+                std::io::stdout(),
+            );
+            let mut context = runtime::Context {
+                fs_access,
+                hook: Some(Box::new(debugger)),
+                ..Default::default()
+            };
+            let mut value_stack = Vec::with_capacity(DEFAULT_STACK_SIZE);
+            match runtime::run(stack, &mut value_stack, &mut context) {
+                Ok(()) => {
+                    let _ = context.host.stdout().flush();
+                    println!("Program finished.\nstack: {value_stack:?}");
+                }
+                Err(error::RuntimeError::Stopped { .. }) => {}
+                Err(err) => {
+                    let _ = context.host.stdout().flush();
+                    println!(
+                        "{}",
+                        runtime_error(
+                            &err,
+                            Some(&path.display().to_string()),
+                            context.location.as_ref()
+                        )
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
 }
 
 fn parse(src: &str, stack: &mut Vec<Stack>, path: Option<PathBuf>) {
+    if let Err(message) = parse_source(src, stack, path) {
+        println!("{message}");
+        std::process::exit(1);
+    }
+}
+
+/// Parses the source, the error is formatted for printing.
+fn parse_source(src: &str, stack: &mut Vec<Stack>, path: Option<PathBuf>) -> Result<(), String> {
     let mut user_definitions = HashMap::with_capacity(DEFAULT_STACK_SIZE);
 
     let mut line_width = 1;
     let mut line_height = 1;
-    if let Err(err) = log_debug_time!(
-        ast::fill(
-            src,
-            stack,
-            &mut line_height,
-            &mut line_width,
-            &mut user_definitions
-        ),
-        "Parsing src"
-    ) {
+    ast::fill_with_imports(
+        src,
+        stack,
+        &mut line_height,
+        &mut line_width,
+        &mut user_definitions,
+        &mut path.clone().map(ast::Imports::new).unwrap_or_default(),
+    )
+    .map_err(|err| {
         const RED: &str = "\x1b[91m";
         const UNDERLINE: &str = "\x1b[4m";
         const BOLD: &str = "\x1b[1m";
         const RESET_FORMATTING: &str = "\x1b[0m";
 
-        println!(
+        format!(
             "{RED}Parsing Error at {UNDERLINE}{BOLD}{}:{line_height}:{line_width}{RESET_FORMATTING}\n{err}",
             path.unwrap_or_default().display()
-        );
-        std::process::exit(1);
+        )
+    })
+}
+
+fn build_error(err: impl std::fmt::Display) -> ! {
+    const RED: &str = "\x1b[91m";
+    const RESET_FORMATTING: &str = "\x1b[0m";
+
+    println!("{RED}Build Error{RESET_FORMATTING}\n{err}");
+    std::process::exit(1);
+}
+
+fn load_binary(path: &PathBuf) -> file::Binary {
+    let src = read_binary(path);
+
+    decode_binary(&src)
+}
+
+fn read_binary(path: &PathBuf) -> Vec<u8> {
+    file::extract_bin(path).unwrap_or_else(|err| file_error("read", path, err))
+}
+
+fn write_output(content: Vec<u8>, path: &PathBuf) {
+    file::write_bin(content, path).unwrap_or_else(|err| file_error("write", path, err));
+}
+
+fn file_error(action: &str, path: &Path, err: std::io::Error) -> ! {
+    const RED: &str = "\x1b[91m";
+    const RESET_FORMATTING: &str = "\x1b[0m";
+
+    println!(
+        "{RED}Could not {action} {}{RESET_FORMATTING}\n\t{err}",
+        path.display()
+    );
+    std::process::exit(1);
+}
+
+fn decode_binary(src: &[u8]) -> file::Binary {
+    match file::Binary::from_bytes(src) {
+        Ok(bin) => bin,
+        Err(err) => {
+            const RED: &str = "\x1b[91m";
+            const RESET_FORMATTING: &str = "\x1b[0m";
+
+            println!("{RED}Binary Error{RESET_FORMATTING}\n{err}");
+            std::process::exit(1);
+        }
     }
 }
 
-fn execute(stack: Vec<Stack>) -> Vec<ValueType> {
+fn execute(
+    stack: Vec<Stack>,
+    mut context: runtime::Context,
+    source_file: Option<String>,
+    options: &cli::RunOptions,
+    mut timings: Timings,
+) {
     let mut value_stack: Vec<ValueType> = Vec::with_capacity(DEFAULT_STACK_SIZE);
 
-    if let Err(err) = log_debug_time!(runtime::run(stack, &mut value_stack), "Executing from ast") {
-        const RED: &str = "\x1b[91m";
-        const RESET_FORMATTING: &str = "\x1b[0m";
-
-        println!("{RED}Runtime Error{RESET_FORMATTING}\n{err}");
+    let result = time_phase!(
+        timings,
+        context.host,
+        Phase::Execute,
+        runtime::run(stack, &mut value_stack, &mut context)
+    );
+    if options.time {
+        let _ = writeln!(context.host.stderr(), "{timings}");
+    }
+    if let Err(err) = result {
+        // The output the program printed before failing comes first.
+        let _ = context.host.stdout().flush();
+        println!(
+            "{}",
+            runtime_error(&err, source_file.as_deref(), context.location.as_ref())
+        );
         std::process::exit(1);
     }
 
-    value_stack
+    if options.print_stack {
+        let _ = writeln!(context.host.stdout(), "{value_stack:?}");
+    }
+}
+
+fn tracer(options: &cli::RunOptions) -> Option<Box<dyn runtime::Hook>> {
+    options.trace.clone().map(|trace| {
+        Box::new(debugger::trace::Tracer::new(std::io::stderr(), trace)) as Box<dyn runtime::Hook>
+    })
+}
+
+fn runtime_error(
+    err: &error::RuntimeError,
+    source_file: Option<&str>,
+    location: Option<&Location>,
+) -> String {
+    const RED: &str = "\x1b[91m";
+    const UNDERLINE: &str = "\x1b[4m";
+    const BOLD: &str = "\x1b[1m";
+    const RESET_FORMATTING: &str = "\x1b[0m";
+
+    match (source_file, location) {
+        (Some(source_file), Some(location)) => format!(
+            "{RED}Runtime Error at {UNDERLINE}{BOLD}{source_file}:{location}{RESET_FORMATTING}\n{err}"
+        ),
+        _ => format!("{RED}Runtime Error{RESET_FORMATTING}\n{err}"),
+    }
 }
@@ -0,0 +1,24 @@
+/*!
+Standard library written in stack_lang and embedded in the interpreter.
+
+Modules are imported by name instead of a path, `"std/math" import` or `"std" import` for all of them.
+*/
+#[cfg(test)]
+mod prelude_test;
+
+/// Names of the modules and their sources.
+pub const MODULES: &[(&str, &str)] = &[
+    ("std", include_str!("std.sl")),
+    ("std/math", include_str!("math.sl")),
+    ("std/list", include_str!("list.sl")),
+    ("std/string", include_str!("string.sl")),
+    ("std/iter", include_str!("iter.sl")),
+];
+
+/// Source of the module with the name.
+pub fn module(name: &str) -> Option<&'static str> {
+    MODULES
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, source)| *source)
+}
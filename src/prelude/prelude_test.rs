@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast,
+    runtime::{testing::run_tests, Context},
+    util::host::BufferedHost,
+};
+
+/// Tests of the modules, written in stack_lang, and what they print.
+const TESTS: &[(&str, &str, &str)] = &[
+    ("math_test.sl", include_str!("math_test.sl"), ""),
+    ("list_test.sl", include_str!("list_test.sl"), ""),
+    (
+        "string_test.sl",
+        include_str!("string_test.sl"),
+        "--- |\n  abab\n",
+    ),
+    ("iter_test.sl", include_str!("iter_test.sl"), ""),
+];
+
+#[test]
+fn test_prelude() {
+    for (name, source, output) in TESTS {
+        let mut stack = Vec::new();
+        ast::fill(source, &mut stack, &mut 1, &mut 1, &mut HashMap::new())
+            .unwrap_or_else(|err| panic!("{name} failed to parse.\n{err}"));
+
+        let host = BufferedHost::default();
+        let mut context = Context {
+            host: Box::new(host.clone()),
+            ..Default::default()
+        };
+        let outcomes = run_tests(stack, &mut context)
+            .unwrap_or_else(|err| panic!("{name} failed to run.\n{err}"));
+
+        assert!(!outcomes.is_empty(), "{name} didn't define any tests.");
+        for outcome in outcomes {
+            if let Some((err, location)) = outcome.failure {
+                panic!("{name}: {} failed at {location:?}.\n{err}", outcome.name);
+            }
+        }
+        assert_eq!(*host.stdout.borrow(), *output, "Output of {name} differs.");
+    }
+}
+
+#[test]
+fn test_prelude_import_all() {
+    let mut user_definitions = HashMap::new();
+    ast::fill(
+        "\"std\" import",
+        &mut Vec::new(),
+        &mut 1,
+        &mut 1,
+        &mut user_definitions,
+    )
+    .unwrap();
+
+    for name in ["abs", "sum", "range", "print_row", "if_else"] {
+        assert!(
+            user_definitions.contains_key(name),
+            "`{name}` isn't imported."
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    runtime::{Context, FsAccess},
+    util::error,
+    Stack, ValueType,
+};
+
+use super::check_argument_count;
+
+/**
+Pops a path from the stack and checks if the context allows touching it.
+
+Checks for:
+- Sufficient argument amount.
+- Valid types.
+- File system access rights.
+ */
+fn pop_allowed_path(
+    stack: &mut Vec<ValueType>,
+    context: &Context,
+) -> error::runtime::Result<PathBuf> {
+    check_argument_count(stack, 1)?;
+
+    let path = match stack.pop().unwrap() {
+        ValueType::Text(path) => PathBuf::from(path),
+        val => {
+            return Err(error::RuntimeError::MismatchedTypes {
+                expected: "Text".to_string(),
+                got: format!("{val:?}"),
+            })
+        }
+    };
+
+    match &context.fs_access {
+        FsAccess::Denied => Err(error::RuntimeError::FsAccessDenied {
+            path: path.display().to_string(),
+            reason: "Run the program with `--allow-fs` to enable file operations.".to_string(),
+        }),
+        FsAccess::Unrestricted => Ok(path),
+        FsAccess::Restricted(root) => {
+            if is_inside(root, &path) {
+                Ok(path)
+            } else {
+                Err(error::RuntimeError::FsAccessDenied {
+                    path: path.display().to_string(),
+                    reason: format!("Only paths inside `{}` are allowed.", root.display()),
+                })
+            }
+        }
+    }
+}
+
+/**
+Checks if the path points inside of root after resolving `..` and symbolic links.
+
+Paths that do not exist yet are resolved through their parent directory.
+ */
+fn is_inside(root: &Path, path: &Path) -> bool {
+    let root = match root.canonicalize() {
+        Ok(root) => root,
+        Err(_) => return false,
+    };
+
+    let resolved = match path.canonicalize() {
+        Ok(resolved) => resolved,
+        Err(_) => {
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+
+            match (parent.canonicalize(), path.file_name()) {
+                (Ok(parent), Some(name)) => parent.join(name),
+                _ => return false,
+            }
+        }
+    };
+
+    resolved.starts_with(root)
+}
+
+fn pop_text(stack: &mut Vec<ValueType>) -> error::runtime::Result<String> {
+    check_argument_count(stack, 1)?;
+
+    match stack.pop().unwrap() {
+        ValueType::Text(text) => Ok(text),
+        val => Err(error::RuntimeError::MismatchedTypes {
+            expected: "Text".to_string(),
+            got: format!("{val:?}"),
+        }),
+    }
+}
+
+#[inline]
+fn failed(path: &Path, err: std::io::Error) -> error::RuntimeError {
+    error::RuntimeError::FsOperationFailed {
+        path: path.display().to_string(),
+        reason: err.to_string(),
+    }
+}
+
+pub fn file_read(stack: &mut Vec<ValueType>, context: &mut Context) -> error::runtime::Result<()> {
+    let path = pop_allowed_path(stack, context)?;
+
+    let content = fs::read_to_string(&path).map_err(|err| failed(&path, err))?;
+    stack.push(ValueType::Text(content));
+
+    Ok(())
+}
+
+pub fn file_write(stack: &mut Vec<ValueType>, context: &mut Context) -> error::runtime::Result<()> {
+    check_argument_count(stack, 2)?;
+
+    let path = pop_allowed_path(stack, context)?;
+    let content = pop_text(stack)?;
+
+    fs::write(&path, content).map_err(|err| failed(&path, err))
+}
+
+pub fn file_append(
+    stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    check_argument_count(stack, 2)?;
+
+    let path = pop_allowed_path(stack, context)?;
+    let content = pop_text(stack)?;
+
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|err| failed(&path, err))
+}
+
+pub fn file_exists(
+    stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    let path = pop_allowed_path(stack, context)?;
+
+    stack.push(ValueType::Bool(path.exists()));
+
+    Ok(())
+}
+
+/**
+Pushes a scope holding names of all entries in the directory in alphabetical order.
+ */
+pub fn dir_list(stack: &mut Vec<ValueType>, context: &mut Context) -> error::runtime::Result<()> {
+    let path = pop_allowed_path(stack, context)?;
+
+    let mut names = fs::read_dir(&path)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+                .collect::<Result<Vec<String>, _>>()
+        })
+        .map_err(|err| failed(&path, err))?;
+    names.sort();
+
+    stack.push(ValueType::Scope(
+        names
+            .into_iter()
+            .map(|name| Stack::Value(ValueType::Text(name)))
+            .collect(),
+    ));
+
+    Ok(())
+}
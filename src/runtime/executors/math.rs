@@ -22,7 +22,7 @@ fn mismatched_args<T: Debug>(expected: &str, got: T) -> error::runtime::Result<(
 A size fits all solution for normalization of number types and common math operations.
 
 Example:
-```
+```ignore
     use crate::ValueType::*;
 
     let mut vec = vec![Int(1),Int(2)];
@@ -1,7 +1,9 @@
+mod fs;
 mod math;
 
-use super::run;
-use crate::{ast::extract::operation::*, util::error, Stack, ValueType};
+use super::{run, Context, Guard};
+use crate::{ast::extract::operation::*, util::error, Effect, Stack, ValueType};
+pub use fs::*;
 use math::*;
 
 #[inline]
@@ -47,42 +49,69 @@ pub fn modulo(stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
     execute_common_math(stack, OperationType::Mod)
 }
 
-pub fn print(stack: &[ValueType]) -> error::runtime::Result<()> {
+fn output_failed(err: std::io::Error) -> error::RuntimeError {
+    error::RuntimeError::OutputFailed {
+        reason: err.to_string(),
+    }
+}
+
+pub fn print(stack: &[ValueType], context: &mut Context) -> error::runtime::Result<()> {
     check_argument_count(stack, 1)?;
 
     let value = &stack[stack.len() - 1];
-    print!("{}", value);
-
-    Ok(())
+    context
+        .host
+        .print(&value.to_string())
+        .map_err(output_failed)
 }
 
-pub fn println(stack: &[ValueType]) -> error::runtime::Result<()> {
+pub fn println(stack: &[ValueType], context: &mut Context) -> error::runtime::Result<()> {
     check_argument_count(stack, 1)?;
 
     let value = &stack[stack.len() - 1];
-    println!("{}", value);
-
-    Ok(())
+    context
+        .host
+        .print(&format!("{}\n", value))
+        .map_err(output_failed)
 }
 
-pub fn print_debug(stack: &[ValueType]) -> error::runtime::Result<()> {
+pub fn print_debug(stack: &[ValueType], context: &mut Context) -> error::runtime::Result<()> {
     check_argument_count(stack, 1)?;
 
     let value = &stack[stack.len() - 1];
-    println!("{:?} is {} element in the stack", value, stack.len());
-
-    Ok(())
+    context
+        .host
+        .print(&format!(
+            "{:?} is {} element in the stack\n",
+            value,
+            stack.len()
+        ))
+        .map_err(output_failed)
 }
 
-pub fn print_debug_stack(stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
+pub fn print_debug_stack(
+    stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
     check_argument_count(stack, 1)?;
 
-    println!("{:#?}", stack);
+    context
+        .host
+        .print(&format!("{:#?}\n", stack))
+        .map_err(output_failed)
+}
 
-    Ok(())
+pub fn print_debug_vars(context: &mut Context) -> error::runtime::Result<()> {
+    context
+        .host
+        .print(&format!("{:#?}\n", context.variables))
+        .map_err(output_failed)
 }
 
-pub fn if_statement(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
+pub fn if_statement(
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
     check_argument_count(value_stack, 2)?;
 
     let condition = value_stack.pop().unwrap();
@@ -90,7 +119,7 @@ pub fn if_statement(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<
 
     if condition.truthy() {
         if let ValueType::Scope(stack) = scope {
-            run(stack.clone(), value_stack)
+            run(stack.clone(), value_stack, context)
         } else {
             Err(error::RuntimeError::MismatchedTypes {
                 expected: "Scope".to_string(),
@@ -102,7 +131,10 @@ pub fn if_statement(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<
     }
 }
 
-pub fn for_loop(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
+pub fn for_loop(
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
     check_argument_count(value_stack, 2)?;
 
     let condition = value_stack.pop().unwrap();
@@ -111,10 +143,22 @@ pub fn for_loop(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()>
     match condition {
         ValueType::Int(range) => {
             if let ValueType::Scope(stack) = scope {
+                // Compiled loops don't count steps, call the hook or save values for `try`,
+                // so they only run without them.
+                #[cfg(feature = "jit")]
+                if context.limits == super::Limits::default()
+                    && context.hook.is_none()
+                    && context.guards.is_empty()
+                    && super::jit::run_for(&stack, range, value_stack, context)
+                {
+                    return Ok(());
+                }
+
                 for _ in 0..range {
+                    context.step(value_stack)?;
                     // ! The scope is copied for every iteration. NOT GOOD
                     //TODO
-                    run(stack.clone(), value_stack)?;
+                    run(stack.clone(), value_stack, context)?;
                 }
             }
         }
@@ -129,7 +173,10 @@ pub fn for_loop(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()>
     Ok(())
 }
 
-pub fn while_loop(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
+pub fn while_loop(
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
     check_argument_count(value_stack, 2)?;
 
     let condition = value_stack.pop().unwrap();
@@ -139,9 +186,10 @@ pub fn while_loop(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()
 
     if let ValueType::Scope(stack) = scope {
         while value_stack[value_stack.len() - 1].truthy() {
+            context.step(value_stack)?;
             // ! The scope is copied for every iteration. NOT GOOD
             // TODO
-            run(stack.clone(), value_stack)?;
+            run(stack.clone(), value_stack, context)?;
         }
     }
 
@@ -258,3 +306,272 @@ pub fn copy(stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
     stack.push(last);
     Ok(())
 }
+
+pub fn enter_macro(
+    effect: &Effect,
+    value_stack: &[ValueType],
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    let needed = effect.inputs.len();
+
+    if value_stack.len() < needed {
+        return Err(error::RuntimeError::MacroArguments {
+            name: effect.name.clone(),
+            effect: effect.to_string(),
+            needed,
+            got: value_stack.len(),
+        });
+    }
+
+    context.macro_frames.push(value_stack.len() - needed);
+
+    Ok(())
+}
+
+pub fn leave_macro(
+    effect: &Effect,
+    value_stack: &[ValueType],
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    let base = context
+        .macro_frames
+        .pop()
+        .expect("Every macro is left after it was entered.");
+    let expected = effect.outputs.len() as i64 - effect.inputs.len() as i64;
+    let got = value_stack.len() as i64 - (base + effect.inputs.len()) as i64;
+
+    if got != expected {
+        return Err(error::RuntimeError::MacroResults {
+            name: effect.name.clone(),
+            effect: effect.to_string(),
+            expected,
+            got,
+        });
+    }
+
+    Ok(())
+}
+
+/**
+Runs the first scope and, if it fails, the handler on top of it.
+
+The handler starts with the stack as it was before the first scope ran,
+with the kind and the message of the error on top.
+*/
+pub fn try_catch(
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 2)?;
+
+    let handler = value_stack.pop().unwrap();
+    let risky = value_stack.pop().unwrap();
+    let (risky, handler) = match (risky, handler) {
+        (ValueType::Scope(risky), ValueType::Scope(handler)) => (risky, handler),
+        (risky, handler) => {
+            return Err(error::RuntimeError::MismatchedTypes {
+                expected: "two Scopes".to_string(),
+                got: format!("{risky:?} and {handler:?}"),
+            })
+        }
+    };
+
+    let macro_frames = context.macro_frames.len();
+    let local_frames = context.local_frames.len();
+    let location = context.location.clone();
+
+    context.guards.push(Guard::new(value_stack.len()));
+    let result = run(risky, value_stack, context);
+    let guard = context
+        .guards
+        .pop()
+        .expect("The guard is removed only by the `try` that added it.");
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) if err.is_fatal() => Err(err),
+        Err(err) => {
+            // Locals are already dropped by `run`, macros the error escaped from have to be left here.
+            guard.restore(value_stack);
+            context.macro_frames.truncate(macro_frames);
+            context.local_frames.truncate(local_frames);
+            context.location = location;
+            value_stack.extend(err.to_values());
+
+            run(handler, value_stack, context)
+        }
+    }
+}
+
+pub fn throw(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 2)?;
+
+    let message = pop_text(value_stack)?;
+    let kind = pop_text(value_stack)?;
+
+    Err(error::RuntimeError::Thrown { kind, message })
+}
+
+pub fn assert(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 1)?;
+
+    let condition = value_stack.pop().unwrap();
+    if !condition.truthy() {
+        return Err(error::RuntimeError::AssertionFailed {
+            reason: format!("The condition is {condition:?}."),
+        });
+    }
+
+    Ok(())
+}
+
+/// Compares the value below with the expected value on top.
+pub fn assert_eq(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 2)?;
+
+    let expected = value_stack.pop().unwrap();
+    let actual = value_stack.pop().unwrap();
+    if actual != expected {
+        return Err(error::RuntimeError::AssertionFailed {
+            reason: format!("Expected {expected:?} but got {actual:?}."),
+        });
+    }
+
+    Ok(())
+}
+
+/// Compares the whole stack with the values the scope on top leaves when it runs on an empty stack.
+pub fn assert_stack(
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 1)?;
+
+    let expected = match value_stack.pop().unwrap() {
+        ValueType::Scope(scope) => {
+            // Guards of `try` belong to the stack of the program, not to this one.
+            let guards = std::mem::take(&mut context.guards);
+            let mut expected = Vec::new();
+            let result = run(scope, &mut expected, context);
+            context.guards = guards;
+            result?;
+
+            expected
+        }
+        value => {
+            return Err(error::RuntimeError::MismatchedTypes {
+                expected: "Scope".to_string(),
+                got: format!("{value:?}"),
+            })
+        }
+    };
+
+    if *value_stack != expected {
+        return Err(error::RuntimeError::AssertionFailed {
+            reason: format!("Expected the stack {expected:?} but it is {value_stack:?}."),
+        });
+    }
+
+    Ok(())
+}
+
+/// Stores the scope on top as a test named by the Text below it.
+pub fn test(value_stack: &mut Vec<ValueType>, context: &mut Context) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 2)?;
+
+    let body = match value_stack.pop().unwrap() {
+        ValueType::Scope(scope) => scope,
+        value => {
+            return Err(error::RuntimeError::MismatchedTypes {
+                expected: "Scope".to_string(),
+                got: format!("{value:?}"),
+            })
+        }
+    };
+    let name = pop_text(value_stack)?;
+
+    context.tests.push(super::testing::Test {
+        name,
+        body,
+        location: context.location.clone(),
+    });
+
+    Ok(())
+}
+
+/**
+Binds the value below the name, to the closest local with that name if there is one.
+
+Inside of an expanded macro only its own locals are looked at, the caller's are left alone.
+*/
+pub fn local_set(
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 2)?;
+
+    let name = pop_text(value_stack)?;
+    let value = value_stack.pop().unwrap();
+    let frame = context.local_frames.last().copied().unwrap_or(0);
+
+    match context.locals[frame..]
+        .iter_mut()
+        .rev()
+        .find(|(local, _)| *local == name)
+    {
+        Some((_, bound)) => *bound = value,
+        None => context.locals.push((name, value)),
+    }
+
+    Ok(())
+}
+
+pub fn local_get(
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 1)?;
+
+    let name = pop_text(value_stack)?;
+
+    match context
+        .locals
+        .iter()
+        .rev()
+        .find(|(local, _)| *local == name)
+    {
+        Some((_, value)) => Ok(value_stack.push(value.clone())),
+        None => Err(error::RuntimeError::UndefinedLocal { name }),
+    }
+}
+
+pub fn set(value_stack: &mut Vec<ValueType>, context: &mut Context) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 2)?;
+
+    let name = pop_text(value_stack)?;
+    let value = value_stack.pop().unwrap();
+    context.variables.insert(name, value);
+
+    Ok(())
+}
+
+pub fn get(value_stack: &mut Vec<ValueType>, context: &mut Context) -> error::runtime::Result<()> {
+    check_argument_count(value_stack, 1)?;
+
+    let name = pop_text(value_stack)?;
+
+    match context.variables.get(&name) {
+        Some(value) => Ok(value_stack.push(value.clone())),
+        None => Err(error::RuntimeError::UndefinedVariable { name }),
+    }
+}
+
+fn pop_text(value_stack: &mut Vec<ValueType>) -> error::runtime::Result<String> {
+    match value_stack.pop().unwrap() {
+        ValueType::Text(name) => Ok(name),
+        value => Err(error::RuntimeError::MismatchedTypes {
+            expected: "Text".to_string(),
+            got: format!("{value:?}"),
+        }),
+    }
+}
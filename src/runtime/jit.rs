@@ -0,0 +1,637 @@
+/*!
+Compiles the bodies of `for` loops to native code with Cranelift.
+
+A body is compiled when every value it touches is statically known to be an Int, a Float or a Bool:
+its literals, the results of its operations and the values it takes from the stack when the loop starts.
+The loop has to leave values of the same types where the next iteration takes them from.
+Everything else, like Texts, Scopes, nested loops, `reverse` or a division by a value
+that could be zero, falls back to the interpreter.
+
+Ints wrap around on overflow, like the interpreter built in release mode.
+*/
+use std::fmt::Debug;
+
+use cranelift_codegen::{
+    ir::{
+        condcodes::{FloatCC, IntCC},
+        types, AbiParam, InstBuilder, MemFlags, Signature, Value,
+    },
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+
+use super::Context;
+use crate::{Location, OperationType, Stack, ValueType};
+
+/// Most values a compiled loop may leave on the stack, longer loops are left to the interpreter.
+const MAX_VALUES: usize = 1 << 24;
+
+/// `(top of the values, iterations, context)`, values are 8 bytes each and start below `top`.
+type LoopFunction = unsafe extern "C" fn(*mut u64, i64, *mut Context);
+
+/// Compiled loops of a single execution, created the first time a loop can be compiled.
+pub struct Jit {
+    /// Runs every loop in the interpreter when `false`.
+    pub enabled: bool,
+    module: Option<JITModule>,
+    loops: Vec<CompiledLoop>,
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Jit {
+            enabled: true,
+            module: None,
+            loops: Vec::new(),
+        }
+    }
+}
+
+impl Debug for Jit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Jit")
+            .field("enabled", &self.enabled)
+            .field("compiled_loops", &self.loops.len())
+            .finish()
+    }
+}
+
+struct CompiledLoop {
+    body: Vec<Stack>,
+    /// Types of the values taken from the stack, from the bottom to the top.
+    inputs: Vec<Type>,
+    /// Types of the values left by an iteration, from the bottom to the top.
+    outputs: Vec<Type>,
+    function: LoopFunction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Int,
+    Float,
+    Bool,
+}
+
+impl Type {
+    fn of(value: &ValueType) -> Option<Type> {
+        match value {
+            ValueType::Int(_) => Some(Type::Int),
+            ValueType::Float(_) => Some(Type::Float),
+            ValueType::Bool(_) => Some(Type::Bool),
+            _ => None,
+        }
+    }
+
+    fn ir(self) -> types::Type {
+        match self {
+            Type::Float => types::F64,
+            Type::Int | Type::Bool => types::I64,
+        }
+    }
+
+    fn bits(value: &ValueType) -> u64 {
+        match value {
+            ValueType::Int(int) => *int as u64,
+            ValueType::Float(float) => float.to_bits(),
+            ValueType::Bool(condition) => *condition as u64,
+            _ => unreachable!("Only numbers and Bools are compiled."),
+        }
+    }
+
+    fn value(self, bits: u64) -> ValueType {
+        match self {
+            Type::Int => ValueType::Int(bits as i64),
+            Type::Float => ValueType::Float(f64::from_bits(bits)),
+            Type::Bool => ValueType::Bool(bits != 0),
+        }
+    }
+}
+
+/**
+Runs `iterations` iterations of the body as native code if it can be compiled.
+
+Gives `false` without touching anything when the loop has to run in the interpreter.
+*/
+pub fn run_for(
+    body: &[Stack],
+    iterations: i64,
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> bool {
+    if !context.jit.enabled || iterations <= 0 || !body.iter().all(supported) {
+        return false;
+    }
+
+    let compiled = match context.jit.find(body, value_stack) {
+        Some(compiled) => compiled,
+        None => match context.jit.compile(body, value_stack) {
+            Some(compiled) => compiled,
+            None => return false,
+        },
+    };
+    let compiled = &context.jit.loops[compiled];
+
+    let taken = compiled.inputs.len();
+    let growth = compiled.outputs.len() - taken;
+    let length = match (growth as u64)
+        .checked_mul(iterations as u64)
+        .and_then(|length| length.checked_add(taken as u64))
+    {
+        Some(length) if length <= MAX_VALUES as u64 => length as usize,
+        _ => return false,
+    };
+
+    let function = compiled.function;
+    let outputs = compiled.outputs.clone();
+
+    let mut values = vec![0u64; length];
+    for (slot, value) in values
+        .iter_mut()
+        .zip(value_stack.drain(value_stack.len() - taken..))
+    {
+        *slot = Type::bits(&value);
+    }
+
+    // The compiled code only hands the context to `jit_print`.
+    unsafe { function(values.as_mut_ptr().add(taken), iterations, context) };
+
+    // Every value was last written by the latest iteration that reached it.
+    value_stack.extend(values.into_iter().enumerate().map(|(index, bits)| {
+        let iteration = match growth {
+            0 => 0,
+            _ => (index / growth).min(iterations as usize - 1),
+        };
+        outputs[index - iteration * growth].value(bits)
+    }));
+
+    if let Some(location) = last_location(body) {
+        context.location = Some(location.clone());
+    }
+
+    true
+}
+
+fn supported(element: &Stack) -> bool {
+    use OperationType::*;
+
+    match element {
+        Stack::Location(_) => true,
+        // Declared stack effects and macro locals are handled by the interpreter.
+        Stack::Effect(_) | Stack::EffectEnd(_) | Stack::LocalFrame | Stack::LocalFrameEnd => false,
+        Stack::Value(value) => Type::of(value).is_some(),
+        Stack::Operation(operation) => matches!(
+            operation,
+            Add | Sub
+                | Mul
+                | Div
+                | Mod
+                | Pow
+                | Lt
+                | Gt
+                | Eq
+                | Leq
+                | Geq
+                | Or
+                | And
+                | Not
+                | Print
+                | Println
+                | Switch
+                | Pop
+                | Copy
+        ),
+    }
+}
+
+fn last_location(body: &[Stack]) -> Option<&Location> {
+    body.iter().rev().find_map(|element| match element {
+        Stack::Location(location) => Some(location),
+        _ => None,
+    })
+}
+
+/// A value of the body, `constant` is known for Int literals.
+#[derive(Clone, Copy)]
+struct Slot {
+    kind: Type,
+    value: Value,
+    constant: Option<i64>,
+}
+
+impl Jit {
+    /// Number of loop bodies compiled so far.
+    pub fn compiled_loops(&self) -> usize {
+        self.loops.len()
+    }
+
+    fn find(&self, body: &[Stack], value_stack: &[ValueType]) -> Option<usize> {
+        self.loops.iter().position(|compiled| {
+            compiled.body == body
+                && compiled.inputs.len() <= value_stack.len()
+                && compiled
+                    .inputs
+                    .iter()
+                    .zip(&value_stack[value_stack.len() - compiled.inputs.len()..])
+                    .all(|(kind, value)| Type::of(value) == Some(*kind))
+        })
+    }
+
+    fn module(&mut self) -> Option<&mut JITModule> {
+        if self.module.is_none() {
+            let mut flags = settings::builder();
+            flags.set("opt_level", "speed").ok()?;
+            let isa = cranelift_native::builder()
+                .ok()?
+                .finish(settings::Flags::new(flags))
+                .ok()?;
+
+            let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+            builder.symbol("jit_print", jit_print as *const u8);
+            builder.symbol("jit_fmod", jit_fmod as *const u8);
+            builder.symbol("jit_powf", jit_powf as *const u8);
+
+            self.module = Some(JITModule::new(builder));
+        }
+
+        self.module.as_mut()
+    }
+
+    /// Compiles the loop for the values currently on the stack and gives its index.
+    fn compile(&mut self, body: &[Stack], value_stack: &[ValueType]) -> Option<usize> {
+        let name = format!("loop_{}", self.loops.len());
+        let module = self.module()?;
+
+        let mut context = module.make_context();
+        let compiled = translate(module, &mut context.func, body, value_stack);
+        let compiled = compiled.and_then(|(inputs, outputs)| {
+            let id = module
+                .declare_function(&name, Linkage::Local, &context.func.signature)
+                .ok()?;
+            module.define_function(id, &mut context).ok()?;
+            Some((id, inputs, outputs))
+        });
+        module.clear_context(&mut context);
+        let (id, inputs, outputs) = compiled?;
+
+        module.finalize_definitions().ok()?;
+        let function = module.get_finalized_function(id);
+
+        self.loops.push(CompiledLoop {
+            body: body.to_vec(),
+            inputs,
+            outputs,
+            // Built with the signature of `LoopFunction` by `translate`.
+            function: unsafe { std::mem::transmute::<*const u8, LoopFunction>(function) },
+        });
+
+        Some(self.loops.len() - 1)
+    }
+}
+
+/**
+Builds the loop function and gives the types it takes and leaves, `None` if the body can't be compiled.
+
+The values taken from the stack are loaded when they are first needed, so the body is only walked once.
+*/
+fn translate(
+    module: &mut JITModule,
+    function: &mut cranelift_codegen::ir::Function,
+    body: &[Stack],
+    value_stack: &[ValueType],
+) -> Option<(Vec<Type>, Vec<Type>)> {
+    let pointer = module.target_config().pointer_type();
+    function.signature.params.extend([
+        AbiParam::new(pointer),
+        AbiParam::new(types::I64),
+        AbiParam::new(pointer),
+    ]);
+
+    let print = import(
+        module,
+        "jit_print",
+        &[pointer, types::I64, types::I64, types::I64],
+        &[],
+    )?;
+    let fmod = import(module, "jit_fmod", &[types::F64, types::F64], &[types::F64])?;
+    let powf = import(module, "jit_powf", &[types::F64, types::F64], &[types::F64])?;
+
+    let mut builder_context = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(function, &mut builder_context);
+    let print = module.declare_func_in_func(print, builder.func);
+    let fmod = module.declare_func_in_func(fmod, builder.func);
+    let powf = module.declare_func_in_func(powf, builder.func);
+
+    let entry = builder.create_block();
+    let header = builder.create_block();
+    let iteration = builder.create_block();
+    let exit = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.append_block_param(header, pointer);
+    builder.append_block_param(header, types::I64);
+    builder.append_block_param(iteration, pointer);
+    builder.append_block_param(iteration, types::I64);
+
+    builder.switch_to_block(entry);
+    let [top, iterations, context] = builder.block_params(entry) else {
+        unreachable!("The signature has three parameters.")
+    };
+    let (top, iterations, context) = (*top, *iterations, *context);
+    builder.ins().jump(header, &[top, iterations]);
+
+    builder.switch_to_block(header);
+    let top = builder.block_params(header)[0];
+    let remaining = builder.block_params(header)[1];
+    let more = builder
+        .ins()
+        .icmp_imm(IntCC::SignedGreaterThan, remaining, 0);
+    builder
+        .ins()
+        .brif(more, iteration, &[top, remaining], exit, &[]);
+
+    builder.switch_to_block(iteration);
+    let top = builder.block_params(iteration)[0];
+    let remaining = builder.block_params(iteration)[1];
+    let flags = MemFlags::trusted();
+
+    let mut stack: Vec<Slot> = Vec::new();
+    // Types of the values taken from the stack, from the top to the bottom.
+    let mut taken: Vec<Type> = Vec::new();
+
+    // Makes sure `needed` values are on the stack, loading them from below the ones taken before.
+    let mut take = |builder: &mut FunctionBuilder, stack: &mut Vec<Slot>, needed: usize| {
+        while stack.len() < needed {
+            let value = value_stack.get(value_stack.len().checked_sub(taken.len() + 1)?)?;
+            let kind = Type::of(value)?;
+            let offset = -8 * (taken.len() as i32 + 1);
+            let value = builder.ins().load(kind.ir(), flags, top, offset);
+
+            taken.push(kind);
+            stack.insert(
+                0,
+                Slot {
+                    kind,
+                    value,
+                    constant: None,
+                },
+            );
+        }
+        Some(())
+    };
+
+    for element in body {
+        let operation = match element {
+            Stack::Location(_) => continue,
+            Stack::Effect(_) | Stack::EffectEnd(_) | Stack::LocalFrame | Stack::LocalFrameEnd => {
+                return None
+            }
+            Stack::Value(value) => {
+                let kind = Type::of(value)?;
+                let (value, constant) = match value {
+                    ValueType::Int(int) => (builder.ins().iconst(types::I64, *int), Some(*int)),
+                    ValueType::Float(float) => (builder.ins().f64const(*float), None),
+                    ValueType::Bool(condition) => {
+                        (builder.ins().iconst(types::I64, *condition as i64), None)
+                    }
+                    _ => return None,
+                };
+                stack.push(Slot {
+                    kind,
+                    value,
+                    constant,
+                });
+                continue;
+            }
+            Stack::Operation(operation) => *operation,
+        };
+
+        use OperationType::*;
+        match operation {
+            Add | Sub | Mul | Div | Mod | Pow => {
+                take(&mut builder, &mut stack, 2)?;
+                let last = stack.pop().unwrap();
+                let next_to_last = stack.pop().unwrap();
+                let (a, b) = (next_to_last.value, last.value);
+                let ins = builder.ins();
+
+                let value = match (next_to_last.kind, last.kind, operation) {
+                    (Type::Int, Type::Int, Add) => ins.iadd(a, b),
+                    (Type::Int, Type::Int, Sub) => ins.isub(a, b),
+                    (Type::Int, Type::Int, Mul) => ins.imul(a, b),
+                    // Dividing by zero or `i64::MIN` by -1 has to fail like in the interpreter.
+                    (Type::Int, Type::Int, Div | Mod) => match last.constant {
+                        Some(0 | -1) | None => return None,
+                        Some(_) if operation == Div => ins.sdiv(a, b),
+                        Some(_) => ins.srem(a, b),
+                    },
+                    (Type::Float, Type::Float, Add) => ins.fadd(a, b),
+                    (Type::Float, Type::Float, Sub) => ins.fsub(a, b),
+                    (Type::Float, Type::Float, Mul) => ins.fmul(a, b),
+                    (Type::Float, Type::Float, Div) => ins.fdiv(a, b),
+                    (Type::Float, Type::Float, Mod | Pow) => {
+                        let callee = if operation == Mod { fmod } else { powf };
+                        let call = ins.call(callee, &[a, b]);
+                        builder.inst_results(call)[0]
+                    }
+                    _ => return None,
+                };
+
+                stack.push(Slot {
+                    kind: next_to_last.kind,
+                    value,
+                    constant: None,
+                });
+            }
+            Lt | Gt | Eq | Leq | Geq => {
+                take(&mut builder, &mut stack, 2)?;
+                let last = stack.pop().unwrap();
+                let next_to_last = stack.pop().unwrap();
+                let (a, b) = (last.value, next_to_last.value);
+
+                let condition = match (last.kind, next_to_last.kind) {
+                    (Type::Int, Type::Int) => {
+                        let code = match operation {
+                            Lt => IntCC::SignedLessThan,
+                            Gt => IntCC::SignedGreaterThan,
+                            Eq => IntCC::Equal,
+                            Leq => IntCC::SignedLessThanOrEqual,
+                            _ => IntCC::SignedGreaterThanOrEqual,
+                        };
+                        builder.ins().icmp(code, a, b)
+                    }
+                    (Type::Float, Type::Float) => {
+                        let code = match operation {
+                            Lt => FloatCC::LessThan,
+                            Gt => FloatCC::GreaterThan,
+                            Eq => FloatCC::Equal,
+                            Leq => FloatCC::LessThanOrEqual,
+                            _ => FloatCC::GreaterThanOrEqual,
+                        };
+                        builder.ins().fcmp(code, a, b)
+                    }
+                    _ => return None,
+                };
+
+                stack.push(boolean(&mut builder, condition));
+            }
+            Or | And => {
+                take(&mut builder, &mut stack, 2)?;
+                let last = stack.pop().unwrap();
+                let next_to_last = stack.pop().unwrap();
+                let a = truthy(&mut builder, last);
+                let b = truthy(&mut builder, next_to_last);
+
+                let condition = if operation == Or {
+                    builder.ins().bor(a, b)
+                } else {
+                    builder.ins().band(a, b)
+                };
+                stack.push(boolean(&mut builder, condition));
+            }
+            Not => {
+                take(&mut builder, &mut stack, 1)?;
+                let slot = stack.pop().unwrap();
+                let truthy = truthy(&mut builder, slot);
+                let ins = builder.ins();
+
+                let value = match slot.kind {
+                    Type::Float => {
+                        let zero = ins.f64const(0.0);
+                        let one = builder.ins().f64const(1.0);
+                        builder.ins().select(truthy, zero, one)
+                    }
+                    Type::Int | Type::Bool => {
+                        let falsy = ins.bxor_imm(truthy, 1);
+                        builder.ins().uextend(types::I64, falsy)
+                    }
+                };
+                stack.push(Slot {
+                    kind: slot.kind,
+                    value,
+                    constant: None,
+                });
+            }
+            Print | Println => {
+                take(&mut builder, &mut stack, 1)?;
+                let slot = stack[stack.len() - 1];
+                let bits = match slot.kind {
+                    Type::Float => builder
+                        .ins()
+                        .bitcast(types::I64, MemFlags::new(), slot.value),
+                    Type::Int | Type::Bool => slot.value,
+                };
+                let kind = builder.ins().iconst(types::I64, slot.kind as i64);
+                let newline = builder
+                    .ins()
+                    .iconst(types::I64, (operation == Println) as i64);
+                builder.ins().call(print, &[context, kind, bits, newline]);
+            }
+            Switch => {
+                take(&mut builder, &mut stack, 2)?;
+                let length = stack.len();
+                stack.swap(length - 1, length - 2);
+            }
+            Pop => {
+                take(&mut builder, &mut stack, 1)?;
+                stack.pop();
+            }
+            Copy => {
+                take(&mut builder, &mut stack, 1)?;
+                stack.push(stack[stack.len() - 1]);
+            }
+            _ => return None,
+        }
+    }
+
+    let inputs: Vec<Type> = taken.into_iter().rev().collect();
+    let outputs: Vec<Type> = stack.iter().map(|slot| slot.kind).collect();
+
+    // The next iteration takes its values from the top of the ones this one left.
+    if outputs.len() < inputs.len() || outputs[outputs.len() - inputs.len()..] != inputs[..] {
+        return None;
+    }
+
+    let bottom = -8 * inputs.len() as i32;
+    for (index, slot) in stack.iter().enumerate() {
+        builder
+            .ins()
+            .store(flags, slot.value, top, bottom + 8 * index as i32);
+    }
+    let growth = 8 * (outputs.len() - inputs.len()) as i64;
+    let top = builder.ins().iadd_imm(top, growth);
+    let remaining = builder.ins().iadd_imm(remaining, -1);
+    builder.ins().jump(header, &[top, remaining]);
+
+    builder.switch_to_block(exit);
+    builder.ins().return_(&[]);
+
+    builder.seal_all_blocks();
+    builder.finalize();
+
+    Some((inputs, outputs))
+}
+
+fn import(
+    module: &mut JITModule,
+    name: &str,
+    params: &[types::Type],
+    returns: &[types::Type],
+) -> Option<FuncId> {
+    let mut signature = Signature::new(module.isa().default_call_conv());
+    signature
+        .params
+        .extend(params.iter().map(|param| AbiParam::new(*param)));
+    signature
+        .returns
+        .extend(returns.iter().map(|returned| AbiParam::new(*returned)));
+
+    module
+        .declare_function(name, Linkage::Import, &signature)
+        .ok()
+}
+
+/// Gives an `i8` that is 1 if the value is truthy.
+fn truthy(builder: &mut FunctionBuilder, slot: Slot) -> Value {
+    match slot.kind {
+        Type::Float => {
+            let zero = builder.ins().f64const(0.0);
+            builder.ins().fcmp(FloatCC::NotEqual, slot.value, zero)
+        }
+        Type::Int | Type::Bool => builder.ins().icmp_imm(IntCC::NotEqual, slot.value, 0),
+    }
+}
+
+fn boolean(builder: &mut FunctionBuilder, condition: Value) -> Slot {
+    Slot {
+        kind: Type::Bool,
+        value: builder.ins().uextend(types::I64, condition),
+        constant: None,
+    }
+}
+
+extern "C" fn jit_print(context: *mut Context, kind: i64, bits: u64, newline: i64) {
+    let kind = match kind {
+        0 => Type::Int,
+        1 => Type::Float,
+        _ => Type::Bool,
+    };
+    // Called only by the loop currently running with this context.
+    let context = unsafe { &mut *context };
+
+    // There's no way to stop the loop from here, failing to write is ignored like a closed pipe.
+    let value = kind.value(bits);
+    let _ = if newline != 0 {
+        context.host.print(&format!("{value}\n"))
+    } else {
+        context.host.print(&value.to_string())
+    };
+}
+
+extern "C" fn jit_fmod(a: f64, b: f64) -> f64 {
+    a % b
+}
+
+extern "C" fn jit_powf(a: f64, b: f64) -> f64 {
+    num::traits::Pow::pow(a, b)
+}
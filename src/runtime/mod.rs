@@ -2,6 +2,9 @@
 mod runtime_test;
 
 mod executors;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod testing;
 
 use self::executors::*;
 use crate::{
@@ -9,25 +12,348 @@ use crate::{
         extract::operation::OperationType::{self, *},
         ValueType,
     },
-    util::error,
-    Stack,
+    util::{
+        error,
+        host::{Host, StdHost},
+    },
+    Location, Stack,
 };
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+/**
+Decides which part of the file system the file operations are allowed to touch.
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum FsAccess {
+    #[default]
+    Denied,
+    Unrestricted,
+    Restricted(PathBuf),
+}
 
-pub fn run(stack: Vec<Stack>, value_stack: &mut Vec<ValueType>) -> error::runtime::Result<()> {
+/**
+Bounds on a single execution, so a program that never ends or keeps growing can be stopped.
+
+Every operation, value and loop iteration is a step. `None` means unlimited.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    /// Largest number of values on the stack.
+    pub max_stack: Option<usize>,
+    /// Largest approximate number of bytes taken by the values on the stack.
+    pub max_memory: Option<usize>,
+    /// Measured from the first step, only enforced if the host has a clock.
+    pub timeout: Option<Duration>,
+}
+
+/**
+Watches the execution of the program, e.g. to debug or trace it.
+
+The hook is taken out of the context while it's called, so it sees the context without itself.
+*/
+pub trait Hook: std::fmt::Debug {
+    /// Called before every operation, `context.location` is the location of the operation.
+    fn before_operation(
+        &mut self,
+        operation: OperationType,
+        value_stack: &[ValueType],
+        context: &Context,
+    ) -> error::runtime::Result<()>;
+
+    /// Called after every operation that succeeded.
+    fn after_operation(
+        &mut self,
+        _operation: OperationType,
+        _value_stack: &[ValueType],
+        _context: &Context,
+    ) -> error::runtime::Result<()> {
+        Ok(())
+    }
+}
+
+/**
+Values a scope guarded by `try` took from below the depth it started at, so they can be put back
+if it fails. They are saved right before the operation that can reach them runs.
+*/
+#[derive(Debug)]
+pub struct Guard {
+    /// Depth of the stack when the guarded scope started.
+    depth: usize,
+    /// Values that were right below `depth`, the deepest one last.
+    saved: Vec<ValueType>,
+}
+
+impl Guard {
+    pub fn new(depth: usize) -> Self {
+        Guard {
+            depth,
+            saved: Vec::new(),
+        }
+    }
+
+    /// Puts the stack back the way it was when the guarded scope started.
+    pub fn restore(self, value_stack: &mut Vec<ValueType>) {
+        value_stack.truncate(self.depth - self.saved.len());
+        value_stack.extend(self.saved.into_iter().rev());
+    }
+}
+
+/**
+State shared by every operation during a single execution of the program.
+*/
+#[derive(Debug)]
+pub struct Context {
+    pub fs_access: FsAccess,
+    /// Location of the most recently reached element, if the program contains them.
+    pub location: Option<Location>,
+    /// Receives everything the program prints.
+    pub host: Box<dyn Host>,
+    /// Depths of the stack below the inputs of the macros with declared effects that are running.
+    pub macro_frames: Vec<usize>,
+    /// Locations of the operations that started the scopes that are running, the innermost last.
+    /// The program itself is the first one.
+    pub scopes: Vec<Option<Location>>,
+    /// Values bound with `->` in the scopes that are running, the innermost last.
+    pub locals: Vec<(String, ValueType)>,
+    /// Where the locals of the expanded macros that are running start, the innermost last.
+    pub local_frames: Vec<usize>,
+    /// Variables stored with `set`, sorted by name so they print in the same order.
+    pub variables: BTreeMap<String, ValueType>,
+    /// Tests defined with `test`, they only run with `testing::run_tests`.
+    pub tests: Vec<testing::Test>,
+    pub limits: Limits,
+    /// Steps taken so far.
+    pub steps: u64,
+    /// When the first step was taken, according to the host.
+    pub started: Option<Duration>,
+    /// Bytes taken by the values on the stack when they were last counted for `Limits::max_memory`.
+    pub memory: usize,
+    /// Upper bound of the bytes the stack grew by since it was counted.
+    pub memory_growth: usize,
+    /// Scopes guarded by `try` that are running, the innermost last.
+    pub guards: Vec<Guard>,
+    /// Watches every operation, see `Hook`.
+    pub hook: Option<Box<dyn Hook>>,
+    /// Native code of the loops compiled so far.
+    #[cfg(feature = "jit")]
+    pub jit: jit::Jit,
+}
+
+/// Writes to the process, see `Context::new` for other hosts.
+impl Default for Context {
+    fn default() -> Self {
+        Context::new(Box::new(StdHost::default()))
+    }
+}
+
+impl Context {
+    /**
+    Context running with the host, without anything else from the environment.
+
+    `Default` creates a `StdHost`, which reads the clock of the process and isn't available
+    everywhere, e.g. on `wasm32-unknown-unknown`.
+    */
+    pub fn new(host: Box<dyn Host>) -> Self {
+        Context {
+            fs_access: FsAccess::default(),
+            location: None,
+            host,
+            macro_frames: Vec::new(),
+            scopes: Vec::new(),
+            locals: Vec::new(),
+            local_frames: Vec::new(),
+            variables: BTreeMap::new(),
+            tests: Vec::new(),
+            limits: Limits::default(),
+            steps: 0,
+            started: None,
+            memory: 0,
+            memory_growth: 0,
+            guards: Vec::new(),
+            hook: None,
+            #[cfg(feature = "jit")]
+            jit: jit::Jit::default(),
+        }
+    }
+
+    /// Counts a step and checks that it stays within the limits.
+    pub fn step(&mut self, value_stack: &[ValueType]) -> error::runtime::Result<()> {
+        self.steps += 1;
+
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(error::RuntimeError::StepLimitExceeded { steps: max_steps });
+            }
+        }
+        if let Some(limit) = self.limits.max_stack {
+            if value_stack.len() > limit {
+                return Err(error::RuntimeError::StackLimitExceeded {
+                    limit,
+                    steps: self.steps,
+                });
+            }
+        }
+        if let Some(limit) = self.limits.max_memory {
+            self.check_memory(value_stack, limit)?;
+        }
+        if let Some(timeout) = self.limits.timeout {
+            let now = self.host.now();
+            if self.started.is_none() {
+                self.started = now;
+            }
+            if let (Some(started), Some(now)) = (self.started, now) {
+                if now - started > timeout {
+                    return Err(error::RuntimeError::Timeout {
+                        timeout,
+                        steps: self.steps,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+    Saves the values the operation can take from below the depths of the guarded scopes.
+
+    Every operation takes at most two values from the top, except `reverse` which moves all of them.
+    Values below the ones saved so far were never reached, so they are still the original ones.
+    */
+    fn guard(&mut self, operation: OperationType, value_stack: &[ValueType]) {
+        let reach = match operation {
+            Reverse => value_stack.len(),
+            _ => 2,
+        };
+        let lowest = value_stack.len().saturating_sub(reach);
+
+        for guard in &mut self.guards {
+            let saved_from = guard.depth - guard.saved.len();
+            if lowest < saved_from {
+                guard
+                    .saved
+                    .extend(value_stack[lowest..saved_from].iter().rev().cloned());
+            }
+        }
+    }
+
+    /**
+    Counting every value on each step would be too slow, so the stack is only counted again
+    when it might have grown past the limit. Values saved for `try` are counted with it.
+
+    An operation pushes at most one value that isn't small, which is on top at the next step,
+    so adding the size of the top value on every step never underestimates the growth.
+    */
+    fn check_memory(
+        &mut self,
+        value_stack: &[ValueType],
+        limit: usize,
+    ) -> error::runtime::Result<()> {
+        self.memory_growth += value_stack.last().map_or(0, ValueType::memory);
+        if self.memory + self.memory_growth <= limit {
+            return Ok(());
+        }
+
+        self.memory = value_stack
+            .iter()
+            .chain(self.guards.iter().flat_map(|guard| &guard.saved))
+            .map(ValueType::memory)
+            .sum();
+        self.memory_growth = 0;
+        if self.memory > limit {
+            return Err(error::RuntimeError::MemoryLimitExceeded {
+                limit,
+                used: self.memory,
+                steps: self.steps,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/**
+Runs the scope, locals bound in it are dropped when it ends.
+*/
+pub fn run(
+    stack: Vec<Stack>,
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
+    let locals = context.locals.len();
+    context.scopes.push(context.location.clone());
+
+    let result = run_elements(stack, value_stack, context);
+    context.locals.truncate(locals);
+    let started = context.scopes.pop().flatten();
+    // Errors keep the location where they happened.
+    if result.is_ok() {
+        context.location = started;
+    }
+
+    result
+}
+
+fn run_elements(
+    stack: Vec<Stack>,
+    value_stack: &mut Vec<ValueType>,
+    context: &mut Context,
+) -> error::runtime::Result<()> {
     for element in stack.into_iter() {
         match element {
-            Stack::Operation(operation) => execute_operation(value_stack, operation)?,
+            Stack::Operation(operation) => {
+                context.step(value_stack)?;
+                call_hook(context, |hook, context| {
+                    hook.before_operation(operation, value_stack, context)
+                })?;
+                context.guard(operation, value_stack);
+                execute_operation(value_stack, operation, context)?;
+                call_hook(context, |hook, context| {
+                    hook.after_operation(operation, value_stack, context)
+                })?
+            }
             // TODO
-            Stack::Value(value) => value_stack.push(value),
+            Stack::Value(value) => {
+                value_stack.push(value);
+                context.step(value_stack)?
+            }
+            Stack::Location(location) => context.location = Some(location),
+            Stack::Effect(effect) => enter_macro(&effect, value_stack, context)?,
+            Stack::EffectEnd(effect) => leave_macro(&effect, value_stack, context)?,
+            Stack::LocalFrame => context.local_frames.push(context.locals.len()),
+            Stack::LocalFrameEnd => {
+                let start = context
+                    .local_frames
+                    .pop()
+                    .expect("Every local frame is left after it was entered.");
+                context.locals.truncate(start);
+            }
         }
     }
 
     Ok(())
 }
 
+fn call_hook(
+    context: &mut Context,
+    call: impl FnOnce(&mut dyn Hook, &Context) -> error::runtime::Result<()>,
+) -> error::runtime::Result<()> {
+    match context.hook.take() {
+        Some(mut hook) => {
+            let result = call(hook.as_mut(), context);
+            context.hook = Some(hook);
+
+            result
+        }
+        None => Ok(()),
+    }
+}
+
 fn execute_operation(
     stack: &mut Vec<ValueType>,
     operation: OperationType,
+    context: &mut Context,
 ) -> error::runtime::Result<()> {
     match operation {
         Add => add(stack),
@@ -47,19 +373,38 @@ fn execute_operation(
         And => and(stack),
         Not => not(stack),
 
-        If => if_statement(stack),
-        For => for_loop(stack),
-        While => while_loop(stack),
+        If => if_statement(stack, context),
+        For => for_loop(stack, context),
+        While => while_loop(stack, context),
+        Try => try_catch(stack, context),
+        Throw => throw(stack),
+
+        Assert => assert(stack),
+        AssertEq => assert_eq(stack),
+        AssertStack => assert_stack(stack, context),
+        Test => test(stack, context),
 
-        Print => print(stack),
-        Println => println(stack),
-        PrintDebug => print_debug(stack),
-        PrintDebugStack => print_debug_stack(stack),
+        Print => print(stack, context),
+        Println => println(stack, context),
+        PrintDebug => print_debug(stack, context),
+        PrintDebugStack => print_debug_stack(stack, context),
+        PrintDebugVars => print_debug_vars(context),
 
         Switch => switch(stack),
         Reverse => reverse(stack),
         Pop => pop(stack),
         Copy => copy(stack),
+
+        FileRead => file_read(stack, context),
+        FileWrite => file_write(stack, context),
+        FileAppend => file_append(stack, context),
+        FileExists => file_exists(stack, context),
+        DirList => dir_list(stack, context),
+
+        LocalSet => local_set(stack, context),
+        LocalGet => local_get(stack, context),
+        Set => set(stack, context),
+        Get => get(stack, context),
         _ => Err(error::RuntimeError::InvalidOperation { operation }),
     }
 }
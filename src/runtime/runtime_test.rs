@@ -1,10 +1,30 @@
 use super::*;
 use crate::ast::extract::operation::*;
 
+fn parse_source(source: &str) -> Vec<Stack> {
+    let mut stack = Vec::new();
+    crate::ast::fill(
+        source,
+        &mut stack,
+        &mut 1,
+        &mut 1,
+        &mut crate::HashMap::new(),
+    )
+    .unwrap();
+    stack
+}
+
+/// Runs `source` with `context` and returns the value stack it left.
+fn run_source(source: &str, context: &mut Context) -> error::runtime::Result<Vec<ValueType>> {
+    let mut value_stack = Vec::new();
+    run(parse_source(source), &mut value_stack, context)?;
+    Ok(value_stack)
+}
+
 #[test]
 fn test_execute_add() {
     let mut stack = vec![ValueType::Int(2), ValueType::Int(2)];
-    execute_operation(&mut stack, OperationType::Add).unwrap();
+    execute_operation(&mut stack, OperationType::Add, &mut Context::default()).unwrap();
 
     assert_eq!(ValueType::Int(4), stack[0]);
 }
@@ -12,7 +32,7 @@ fn test_execute_add() {
 #[test]
 fn test_execute_sub() {
     let mut stack = vec![ValueType::Float(2.), ValueType::Float(2.5)];
-    execute_operation(&mut stack, OperationType::Sub).unwrap();
+    execute_operation(&mut stack, OperationType::Sub, &mut Context::default()).unwrap();
 
     assert_eq!(ValueType::Float(-0.5), stack[0]);
 }
@@ -20,7 +40,7 @@ fn test_execute_sub() {
 #[test]
 fn test_execute_mul() {
     let mut stack = vec![ValueType::Float(1.5), ValueType::Float(2.0)];
-    execute_operation(&mut stack, OperationType::Mul).unwrap();
+    execute_operation(&mut stack, OperationType::Mul, &mut Context::default()).unwrap();
 
     assert_eq!(ValueType::Float(3.0), stack[0]);
 }
@@ -28,7 +48,7 @@ fn test_execute_mul() {
 #[test]
 fn test_execute_div() {
     let mut stack = vec![ValueType::Int(5), ValueType::Int(2)];
-    execute_operation(&mut stack, OperationType::Div).unwrap();
+    execute_operation(&mut stack, OperationType::Div, &mut Context::default()).unwrap();
 
     assert_eq!(ValueType::Int(2), stack[0]);
 }
@@ -36,7 +56,7 @@ fn test_execute_div() {
 #[test]
 fn test_execute_div_by_zero() {
     let mut stack = vec![ValueType::Int(0), ValueType::Int(5)];
-    execute_operation(&mut stack, OperationType::Div).unwrap();
+    execute_operation(&mut stack, OperationType::Div, &mut Context::default()).unwrap();
 
     assert_eq!(ValueType::Int(0), stack[0]);
 }
@@ -45,7 +65,7 @@ fn test_execute_div_by_zero() {
 #[should_panic]
 fn test_execute_mul_mixed_types() {
     let mut stack = vec![ValueType::Float(5.0), ValueType::Int(0)];
-    execute_operation(&mut stack, OperationType::Div).unwrap();
+    execute_operation(&mut stack, OperationType::Div, &mut Context::default()).unwrap();
 
     assert_eq!(ValueType::Int(2), stack[0]);
 }
@@ -53,7 +73,7 @@ fn test_execute_mul_mixed_types() {
 #[test]
 fn test_execute_pow() {
     let mut stack = vec![ValueType::Int(2), ValueType::Int(3)];
-    execute_operation(&mut stack, OperationType::Pow).unwrap();
+    execute_operation(&mut stack, OperationType::Pow, &mut Context::default()).unwrap();
 
     assert_eq!(ValueType::Int(8), stack[0]);
 }
@@ -61,21 +81,84 @@ fn test_execute_pow() {
 #[test]
 fn test_execute_mod() {
     let mut stack = vec![ValueType::Int(3), ValueType::Int(2)];
-    execute_operation(&mut stack, OperationType::Mod).unwrap();
+    execute_operation(&mut stack, OperationType::Mod, &mut Context::default()).unwrap();
 
     assert_eq!(ValueType::Int(1), stack[0]);
 }
 
 #[test]
 fn test_execute_print() {
+    let host = crate::util::host::BufferedHost::default();
+    let mut context = Context {
+        host: Box::new(host.clone()),
+        ..Default::default()
+    };
+
     let mut stack = vec![ValueType::Text("Hell'o, World!".to_string())];
-    execute_operation(&mut stack, OperationType::Print).unwrap();
+    execute_operation(&mut stack, OperationType::Print, &mut context).unwrap();
+    execute_operation(&mut stack, OperationType::Println, &mut context).unwrap();
+
+    assert_eq!(*host.stdout.borrow(), "Hell'o, World!Hell'o, World!\n");
+    assert_eq!(*host.stderr.borrow(), "");
 }
 
 #[test]
 fn test_execute_print_debug() {
+    let host = crate::util::host::BufferedHost::default();
+    let mut context = Context {
+        host: Box::new(host.clone()),
+        ..Default::default()
+    };
+
     let mut stack = vec![ValueType::Text("Hell'o, World!".to_string())];
-    execute_operation(&mut stack, OperationType::PrintDebug).unwrap();
+    execute_operation(&mut stack, OperationType::PrintDebug, &mut context).unwrap();
+
+    assert_eq!(
+        *host.stdout.borrow(),
+        "Text(\"Hell'o, World!\") is 1 element in the stack\n"
+    );
+}
+
+#[test]
+fn test_execute_print_fails() {
+    /// Host whose stdout is closed.
+    #[derive(Debug)]
+    struct Closed(std::io::Sink);
+
+    impl crate::util::host::Host for Closed {
+        fn stdout(&mut self) -> &mut dyn std::io::Write {
+            self
+        }
+
+        fn stderr(&mut self) -> &mut dyn std::io::Write {
+            &mut self.0
+        }
+
+        fn now(&self) -> Option<std::time::Duration> {
+            None
+        }
+    }
+
+    impl std::io::Write for Closed {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut context = Context {
+        host: Box::new(Closed(std::io::sink())),
+        ..Default::default()
+    };
+    let mut stack = vec![ValueType::Int(1)];
+
+    assert!(matches!(
+        execute_operation(&mut stack, OperationType::Println, &mut context),
+        Err(error::RuntimeError::OutputFailed { .. })
+    ));
 }
 
 #[test]
@@ -87,7 +170,7 @@ fn test_execute_if_statement_true() {
         ValueType::Bool(true),
     ];
 
-    execute_operation(&mut stack, OperationType::If).unwrap();
+    execute_operation(&mut stack, OperationType::If, &mut Context::default()).unwrap();
 
     assert_eq!(stack, vec![ValueType::Int(2)]);
 }
@@ -101,7 +184,7 @@ fn test_execute_if_statement_false() {
         ValueType::Bool(false),
     ];
 
-    execute_operation(&mut stack, OperationType::If).unwrap();
+    execute_operation(&mut stack, OperationType::If, &mut Context::default()).unwrap();
 
     assert_eq!(stack, vec![ValueType::Int(1), ValueType::Int(2)]);
 }
@@ -110,7 +193,7 @@ fn test_execute_if_statement_false() {
 fn test_execute_lt() {
     let mut stack = vec![ValueType::Int(2), ValueType::Int(1)];
 
-    execute_operation(&mut stack, OperationType::Lt).unwrap();
+    execute_operation(&mut stack, OperationType::Lt, &mut Context::default()).unwrap();
 
     // 1 is less than 2
     assert_eq!(stack, vec![ValueType::Bool(true)]);
@@ -120,7 +203,7 @@ fn test_execute_lt() {
 fn test_execute_gt() {
     let mut stack = vec![ValueType::Int(1), ValueType::Int(2)];
 
-    execute_operation(&mut stack, OperationType::Gt).unwrap();
+    execute_operation(&mut stack, OperationType::Gt, &mut Context::default()).unwrap();
 
     // 2 is grater than 1
     assert_eq!(stack, vec![ValueType::Bool(true)]);
@@ -130,7 +213,7 @@ fn test_execute_gt() {
 fn test_execute_eq() {
     let mut stack = vec![ValueType::Int(5), ValueType::Int(5)];
 
-    execute_operation(&mut stack, OperationType::Eq).unwrap();
+    execute_operation(&mut stack, OperationType::Eq, &mut Context::default()).unwrap();
 
     // 5 is equal to 5
     assert_eq!(stack, vec![ValueType::Bool(true)]);
@@ -140,7 +223,7 @@ fn test_execute_eq() {
 fn test_execute_leq() {
     let mut stack = vec![ValueType::Int(5), ValueType::Int(5)];
 
-    execute_operation(&mut stack, OperationType::Leq).unwrap();
+    execute_operation(&mut stack, OperationType::Leq, &mut Context::default()).unwrap();
 
     // 5 is equal or less than 5
     assert_eq!(stack, vec![ValueType::Bool(true)]);
@@ -150,7 +233,7 @@ fn test_execute_leq() {
 fn test_execute_geq() {
     let mut stack = vec![ValueType::Int(5), ValueType::Int(5)];
 
-    execute_operation(&mut stack, OperationType::Geq).unwrap();
+    execute_operation(&mut stack, OperationType::Geq, &mut Context::default()).unwrap();
 
     // 5 is equal or grater than 5
     assert_eq!(stack, vec![ValueType::Bool(true)]);
@@ -160,7 +243,7 @@ fn test_execute_geq() {
 fn test_execute_or() {
     let mut stack = vec![ValueType::Bool(false), ValueType::Bool(true)];
 
-    execute_operation(&mut stack, OperationType::Or).unwrap();
+    execute_operation(&mut stack, OperationType::Or, &mut Context::default()).unwrap();
 
     // one of: [false, true] is true
     assert_eq!(stack, vec![ValueType::Bool(true)]);
@@ -170,7 +253,7 @@ fn test_execute_or() {
 fn test_execute_and() {
     let mut stack = vec![ValueType::Bool(true), ValueType::Bool(true)];
 
-    execute_operation(&mut stack, OperationType::Or).unwrap();
+    execute_operation(&mut stack, OperationType::Or, &mut Context::default()).unwrap();
 
     // all of: [true, true] are true
     assert_eq!(stack, vec![ValueType::Bool(true)]);
@@ -180,7 +263,7 @@ fn test_execute_and() {
 fn test_execute_not() {
     let mut stack = vec![ValueType::Bool(true)];
 
-    execute_operation(&mut stack, OperationType::Not).unwrap();
+    execute_operation(&mut stack, OperationType::Not, &mut Context::default()).unwrap();
 
     assert_eq!(stack, vec![ValueType::Bool(false)]);
 }
@@ -197,7 +280,7 @@ fn test_execute_for() {
         ValueType::Int(3),
     ];
 
-    execute_operation(&mut stack, OperationType::For).unwrap();
+    execute_operation(&mut stack, OperationType::For, &mut Context::default()).unwrap();
 
     // 2 + 3 = 5
     assert_eq!(vec![ValueType::Int(5)], stack);
@@ -214,7 +297,7 @@ fn test_execute_while() {
         ValueType::Int(3),
     ];
 
-    execute_operation(&mut stack, OperationType::While).unwrap();
+    execute_operation(&mut stack, OperationType::While, &mut Context::default()).unwrap();
 
     // top value needs to be falsy to stop execution
     assert_eq!(vec![ValueType::Int(0)], stack);
@@ -224,7 +307,7 @@ fn test_execute_while() {
 fn test_execute_switch() {
     let mut stack = vec![ValueType::Int(1), ValueType::Int(2), ValueType::Int(3)];
 
-    execute_operation(&mut stack, OperationType::Switch).unwrap();
+    execute_operation(&mut stack, OperationType::Switch, &mut Context::default()).unwrap();
 
     assert_eq!(
         vec![ValueType::Int(1), ValueType::Int(3), ValueType::Int(2)],
@@ -236,7 +319,7 @@ fn test_execute_switch() {
 fn test_execute_reverse() {
     let mut stack = vec![ValueType::Int(1), ValueType::Int(2), ValueType::Int(3)];
 
-    execute_operation(&mut stack, OperationType::Reverse).unwrap();
+    execute_operation(&mut stack, OperationType::Reverse, &mut Context::default()).unwrap();
 
     assert_eq!(
         vec![ValueType::Int(3), ValueType::Int(2), ValueType::Int(1)],
@@ -248,7 +331,7 @@ fn test_execute_reverse() {
 fn test_execute_pop() {
     let mut stack = vec![ValueType::Int(1), ValueType::Int(2), ValueType::Int(3)];
 
-    execute_operation(&mut stack, OperationType::Pop).unwrap();
+    execute_operation(&mut stack, OperationType::Pop, &mut Context::default()).unwrap();
 
     assert_eq!(vec![ValueType::Int(1), ValueType::Int(2)], stack);
 }
@@ -257,7 +340,489 @@ fn test_execute_pop() {
 fn test_execute_copy() {
     let mut stack = vec![ValueType::Int(1)];
 
-    execute_operation(&mut stack, OperationType::Copy).unwrap();
+    execute_operation(&mut stack, OperationType::Copy, &mut Context::default()).unwrap();
 
     assert_eq!(vec![ValueType::Int(1), ValueType::Int(1)], stack);
 }
+
+#[test]
+fn test_execute_file_operations_denied_by_default() {
+    let mut stack = vec![ValueType::Text("Cargo.toml".to_string())];
+
+    let result = execute_operation(&mut stack, OperationType::FileRead, &mut Context::default());
+
+    assert!(matches!(
+        result,
+        Err(error::RuntimeError::FsAccessDenied { .. })
+    ));
+}
+
+#[test]
+fn test_execute_file_operations_restricted() {
+    // Unique to the process, so runs at the same time don't share the directory.
+    let root = std::env::temp_dir().join(format!(
+        "stack_lang_test_execute_file_operations_restricted_{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&root).unwrap();
+    let file = root.join("notes.txt").display().to_string();
+
+    let mut context = Context {
+        fs_access: FsAccess::Restricted(root.clone()),
+        ..Default::default()
+    };
+
+    let mut stack = vec![
+        ValueType::Text("Hell'o".to_string()),
+        ValueType::Text(file.clone()),
+    ];
+    execute_operation(&mut stack, OperationType::FileWrite, &mut context).unwrap();
+
+    let mut stack = vec![
+        ValueType::Text(", World!".to_string()),
+        ValueType::Text(file.clone()),
+    ];
+    execute_operation(&mut stack, OperationType::FileAppend, &mut context).unwrap();
+
+    let mut stack = vec![ValueType::Text(file.clone())];
+    execute_operation(&mut stack, OperationType::FileRead, &mut context).unwrap();
+    assert_eq!(vec![ValueType::Text("Hell'o, World!".to_string())], stack);
+
+    let mut stack = vec![ValueType::Text(file)];
+    execute_operation(&mut stack, OperationType::FileExists, &mut context).unwrap();
+    assert_eq!(vec![ValueType::Bool(true)], stack);
+
+    let mut stack = vec![ValueType::Text(root.display().to_string())];
+    execute_operation(&mut stack, OperationType::DirList, &mut context).unwrap();
+    assert_eq!(
+        vec![ValueType::Scope(vec![Stack::Value(ValueType::Text(
+            "notes.txt".to_string()
+        ))])],
+        stack
+    );
+
+    let mut stack = vec![ValueType::Text(
+        root.join("..").join("escaped.txt").display().to_string(),
+    )];
+    let result = execute_operation(&mut stack, OperationType::FileExists, &mut context);
+    assert!(matches!(
+        result,
+        Err(error::RuntimeError::FsAccessDenied { .. })
+    ));
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_execute_macro_effects() {
+    let mut context = Context::default();
+    assert_eq!(
+        run_source(
+            "{copy *} \"square\" ( n -- n ) macro 3 square square",
+            &mut context
+        )
+        .unwrap(),
+        vec![ValueType::Int(81)]
+    );
+    assert!(context.macro_frames.is_empty());
+    assert!(matches!(
+        run_source("{copy *} \"square\" ( n -- n ) macro square", &mut Context::default()),
+        Err(error::RuntimeError::MacroArguments { name, needed: 1, got: 0, .. }) if name == "square"
+    ));
+    // Whether the value is popped depends on the condition, so only the runtime can tell.
+    assert!(matches!(
+        run_source(
+            "{{pop} switch if} \"drop_if\" ( a c -- ) macro 1 false drop_if",
+            &mut Context::default()
+        ),
+        Err(error::RuntimeError::MacroResults { name, expected: -2, got: -1, .. }) if name == "drop_if"
+    ));
+}
+
+#[test]
+fn test_execute_locals() {
+    let run_source = |source: &str| {
+        let mut context = Context::default();
+        run_source(source, &mut context).map(|value_stack| (value_stack, context.locals))
+    };
+
+    assert_eq!(
+        run_source("3 -> x 4 -> y x y * x -").unwrap(),
+        (vec![ValueType::Int(9)], vec![])
+    );
+    // Every iteration binds `i` again and updates `sum` of the enclosing scope.
+    assert_eq!(
+        run_source("0 -> sum 1 { copy -> i sum i + -> sum 1 + } 4 for pop sum").unwrap(),
+        (vec![ValueType::Int(10)], vec![])
+    );
+    assert_eq!(
+        run_source("1 -> x { x 1 + -> x { x 10 * } true if } true if x").unwrap(),
+        (vec![ValueType::Int(20), ValueType::Int(2)], vec![])
+    );
+    // Macros bind locals of their own, the caller's `a` keeps its value.
+    assert_eq!(
+        run_source("{ -> a a a * } \"sq\" macro 5 -> a 3 sq a").unwrap(),
+        (vec![ValueType::Int(9), ValueType::Int(5)], vec![])
+    );
+    assert_eq!(
+        run_source("{ -> n { n 1 + -> n } 3 for n } \"add3\" macro 1 -> n 10 add3 n").unwrap(),
+        (vec![ValueType::Int(13), ValueType::Int(1)], vec![])
+    );
+    // The macro reads `inner` after the scope that bound it ended.
+    assert!(matches!(
+        run_source("{ 5 -> inner { inner } \"read\" macro } true if read"),
+        Err(error::RuntimeError::UndefinedLocal { name }) if name == "inner"
+    ));
+}
+
+#[test]
+fn test_execute_variables() {
+    let host = crate::util::host::BufferedHost::default();
+    let run_source = |source: &str| run_source(source, &mut Context::new(Box::new(host.clone())));
+
+    assert_eq!(
+        run_source("0 \"count\" set { \"count\" get 1 + \"count\" set } 5 for \"count\" get")
+            .unwrap(),
+        vec![ValueType::Int(5)]
+    );
+    // Unlike locals, variables outlive the scope that set them.
+    assert_eq!(
+        run_source("{ \"inner\" \"name\" set } true if \"name\" get").unwrap(),
+        vec![ValueType::Text("inner".to_string())]
+    );
+    assert!(matches!(
+        run_source("\"missing\" get"),
+        Err(error::RuntimeError::UndefinedVariable { name }) if name == "missing"
+    ));
+    assert!(matches!(
+        run_source("1 2 set"),
+        Err(error::RuntimeError::MismatchedTypes { .. })
+    ));
+
+    host.stdout.borrow_mut().clear();
+    run_source("2 \"b\" set true \"a\" set print_debug_vars").unwrap();
+    assert_eq!(
+        *host.stdout.borrow(),
+        "{\n    \"a\": Bool(\n        true,\n    ),\n    \"b\": Int(\n        2,\n    ),\n}\n"
+    );
+}
+
+#[test]
+fn test_execute_try() {
+    let run_source = |source: &str| {
+        let mut context = Context::default();
+        run_source(source, &mut context).map(|value_stack| (value_stack, context))
+    };
+    let text = |text: &str| ValueType::Text(text.to_string());
+
+    // The stack is restored before the handler runs, also the values the failed scope took.
+    let (stack, _) = run_source("1 2 { pop pop \"a\" 1 + } { } try").unwrap();
+    assert_eq!(
+        stack,
+        vec![
+            ValueType::Int(1),
+            ValueType::Int(2),
+            text("MismatchedTypes"),
+            text("Expected a numeric type but got Text(\"a\")."),
+        ]
+    );
+    // Only what the scope reached is saved, also by `reverse`, loops, nested `try`s and other stacks.
+    for source in [
+        "1 2 3 { reverse pop pop 4 5 { pop } 3 for \"a\" 1 + } { pop pop } try",
+        "1 2 3 { pop { pop \"a\" 1 + } { pop pop } try pop \"b\" 1 + } { pop pop } try",
+        "1 2 3 { pop { 7 8 pop } assert_stack } { pop pop } try",
+    ] {
+        let (stack, context) = run_source(source).unwrap();
+        assert_eq!(
+            stack,
+            vec![ValueType::Int(1), ValueType::Int(2), ValueType::Int(3)],
+            "{source}"
+        );
+        assert!(context.guards.is_empty());
+    }
+    let (stack, _) = run_source("{ 5 } { \"unused\" } try").unwrap();
+    assert_eq!(stack, vec![ValueType::Int(5)]);
+    let (stack, _) = run_source("{ \"Custom\" \"message\" throw } { } try").unwrap();
+    assert_eq!(stack, vec![text("Custom"), text("message")]);
+
+    // Macros and locals the error escaped from are left.
+    let (stack, context) =
+        run_source("{ \"a\" + } \"broken\" ( a -- sum ) macro { 1 -> x 1 broken } { pop pop } try")
+            .unwrap();
+    assert_eq!(stack, vec![]);
+    assert!(context.macro_frames.is_empty());
+    assert!(context.locals.is_empty());
+    let (stack, context) =
+        run_source("{ -> a a \"x\" + } \"broken\" macro 5 -> a { 1 broken } { pop pop } try a")
+            .unwrap();
+    assert_eq!(stack, vec![ValueType::Int(5)]);
+    assert!(context.local_frames.is_empty());
+
+    assert!(matches!(
+        run_source("{ \"Inner\" \"x\" throw } { switch pop \"Outer\" switch throw } try"),
+        Err(error::RuntimeError::Thrown { kind, message }) if kind == "Outer" && message == "x"
+    ));
+}
+
+#[test]
+fn test_execute_limits() {
+    let run_source = |source: &str, limits: Limits| {
+        let mut context = Context {
+            limits,
+            ..Default::default()
+        };
+        run_source(source, &mut context).map(|_| context.steps)
+    };
+
+    let max_steps = Limits {
+        max_steps: Some(100),
+        ..Default::default()
+    };
+    // Values, operations and loop iterations are steps.
+    assert_eq!(run_source("{ 1 pop } 3 for", max_steps).unwrap(), 12);
+    assert!(matches!(
+        run_source("{ } true while", max_steps),
+        Err(error::RuntimeError::StepLimitExceeded { steps: 100 })
+    ));
+
+    let max_stack = Limits {
+        max_stack: Some(10),
+        ..Default::default()
+    };
+    assert!(matches!(
+        run_source("1 { copy } 1000000 for", max_stack),
+        Err(error::RuntimeError::StackLimitExceeded {
+            limit: 10,
+            steps: 25
+        })
+    ));
+
+    let max_memory = Limits {
+        max_memory: Some(4096),
+        ..Default::default()
+    };
+    assert!(run_source("\"text\" { copy pop } 1000 for", max_memory).is_ok());
+    assert!(matches!(
+        run_source("\"text\" { copy } 1000 for", max_memory),
+        Err(error::RuntimeError::MemoryLimitExceeded { limit: 4096, used, .. }) if used > 4096
+    ));
+
+    let timeout = Limits {
+        timeout: Some(std::time::Duration::from_millis(10)),
+        ..Default::default()
+    };
+    assert!(matches!(
+        run_source("{ } true while", timeout),
+        Err(error::RuntimeError::Timeout { .. })
+    ));
+
+    // The program can't catch them.
+    assert!(matches!(
+        run_source("{ { } true while } { } try", max_steps),
+        Err(error::RuntimeError::StepLimitExceeded { .. })
+    ));
+}
+
+#[test]
+fn test_execute_hook() {
+    /// Records the operations with the scope depth and the stack around them.
+    #[derive(Debug, Default)]
+    struct Recorder(std::rc::Rc<std::cell::RefCell<Vec<String>>>);
+
+    impl Hook for Recorder {
+        fn before_operation(
+            &mut self,
+            operation: OperationType,
+            value_stack: &[ValueType],
+            context: &Context,
+        ) -> error::runtime::Result<()> {
+            self.0.borrow_mut().push(format!(
+                "{} {} {value_stack:?}",
+                context.scopes.len(),
+                operation.keyword()
+            ));
+            Ok(())
+        }
+
+        fn after_operation(
+            &mut self,
+            _operation: OperationType,
+            value_stack: &[ValueType],
+            _context: &Context,
+        ) -> error::runtime::Result<()> {
+            self.0.borrow_mut().push(format!("-> {value_stack:?}"));
+            Ok(())
+        }
+    }
+
+    let stack = parse_source("1 { 2 * } true if");
+
+    let recorder = Recorder::default();
+    let records = recorder.0.clone();
+    let mut context = Context {
+        hook: Some(Box::new(recorder)),
+        ..Default::default()
+    };
+    run(stack, &mut Vec::new(), &mut context).unwrap();
+
+    assert_eq!(
+        *records.borrow(),
+        [
+            "1 if [Int(1), Scope([Value(Int(2)), Operation(Mul)]), Bool(true)]",
+            "2 * [Int(1), Int(2)]",
+            "-> [Int(2)]",
+            "-> [Int(2)]",
+        ]
+    );
+    assert!(context.scopes.is_empty());
+}
+
+#[test]
+fn test_execute_assertions() {
+    let run_source = |source: &str| run_source(source, &mut Context::default());
+
+    assert!(run_source("1 assert 2 2 assert_eq 1 2 { 2 1 - 2 } assert_stack").is_ok());
+    // Scopes written at different places are equal.
+    assert!(run_source("{ 1 + }\n{ 1 + } assert_eq").is_ok());
+    assert!(run_source("{ 2 * } { { 2 * } } assert_stack").is_ok());
+    assert!(run_source("{ 1 + } \"inc\" ( n -- n ) macro { inc }\n  { inc } assert_eq").is_ok());
+    for source in [
+        "0 assert",
+        "1 2 assert_eq",
+        "1 1.0 assert_eq",
+        "1 2 { 2 1 } assert_stack",
+    ] {
+        assert!(
+            matches!(
+                run_source(source),
+                Err(error::RuntimeError::AssertionFailed { .. })
+            ),
+            "`{source}` passed."
+        );
+    }
+}
+
+#[test]
+fn test_run_tests() {
+    let stack = parse_source(
+        r#"
+            1 "x" set
+            "passes" { "x" get 1 assert_eq 2 "x" set 5 } test
+            "sees the original variables" { "x" get 1 assert_eq } test
+            "fails" { 1 2 assert_eq } test
+            "starts with an empty stack" { { } assert_stack } test
+        "#,
+    );
+
+    let outcomes = testing::run_tests(stack, &mut Context::default()).unwrap();
+
+    let results: Vec<(&str, bool)> = outcomes
+        .iter()
+        .map(|outcome| (outcome.name.as_str(), outcome.passed()))
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            ("passes", true),
+            ("sees the original variables", true),
+            ("fails", false),
+            ("starts with an empty stack", true),
+        ]
+    );
+    assert!(matches!(
+        outcomes[2].failure,
+        Some((
+            error::RuntimeError::AssertionFailed { .. },
+            Some(Location { line: 5, .. })
+        ))
+    ));
+}
+
+#[test]
+fn test_run_tests_limits() {
+    let stack = parse_source(
+        r#"
+            "first" { 1 1 + 1 + 1 + pop } test
+            "second" { 1 1 + 1 + 1 + pop } test
+            "loops" { { } true while } test
+        "#,
+    );
+    let mut context = Context {
+        limits: Limits {
+            max_steps: Some(20),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let outcomes = testing::run_tests(stack, &mut context).unwrap();
+
+    assert!(outcomes[0].passed());
+    assert!(outcomes[1].passed());
+    assert!(matches!(
+        outcomes[2].failure,
+        Some((error::RuntimeError::StepLimitExceeded { steps: 20 }, _))
+    ));
+}
+
+#[cfg(feature = "jit")]
+fn run_with_jit(source: &str, jit: bool) -> (Vec<ValueType>, String, bool, usize) {
+    let host = crate::util::host::BufferedHost::default();
+    let mut context = Context::new(Box::new(host.clone()));
+    context.jit.enabled = jit;
+
+    let mut value_stack = Vec::new();
+    let result = run(parse_source(source), &mut value_stack, &mut context);
+    let stdout = host.stdout.take();
+
+    (
+        value_stack,
+        stdout,
+        result.is_ok(),
+        context.jit.compiled_loops(),
+    )
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn test_jit_matches_interpreter() {
+    for source in [
+        "0 {1 + copy 3 * 7 % pop} 1000 for",
+        "{1 2 3 4 5 6 7 8 9 + + + + + + + + 10 * println} 4 for",
+        "1.5 {2.0 * 3.0 % copy 0.5 < ! pop 1.25 ^} 5 for",
+        "0 {copy print 1 + copy 2 < 0.5 ! && pop} 3 for",
+        "1 2 {switch copy 10 -3 / +} 6 for",
+        "1.0 {0.0 / println pop 1.0} 2 for",
+    ] {
+        let interpreted = run_with_jit(source, false);
+        let compiled = run_with_jit(source, true);
+
+        assert_eq!(interpreted.0, compiled.0, "{source}");
+        assert_eq!(interpreted.1, compiled.1, "{source}");
+        assert!(compiled.2, "{source}");
+        assert_eq!(1, compiled.3, "{source}");
+    }
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn test_jit_falls_back_to_interpreter() {
+    for (source, succeeds) in [
+        ("2 3 {copy reverse +} 5 for", true),
+        ("\"a\" {copy} 3 for", true),
+        ("1 {copy {1 +} 2 for} 2 for", true),
+        ("10 {2 /} 3 for 0 {1 +} 0 for", true),
+        ("1 2 {switch 1.0 +} 2 for", false),
+        ("{1 +} 3 for", false),
+    ] {
+        let interpreted = run_with_jit(source, false);
+        let compiled = run_with_jit(source, true);
+
+        assert_eq!(interpreted.0, compiled.0, "{source}");
+        assert_eq!(interpreted.2, compiled.2, "{source}");
+        assert_eq!(succeeds, compiled.2, "{source}");
+    }
+
+    assert_eq!(0, run_with_jit("2 3 {copy reverse +} 5 for", true).3);
+    assert_eq!(1, run_with_jit("1 {copy {1 +} 2 for} 2 for", true).3);
+}
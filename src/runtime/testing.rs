@@ -0,0 +1,66 @@
+/*!
+Running the tests defined with `"name" { body } test`.
+*/
+use super::{run, Context};
+use crate::{util::error, Location, Stack};
+
+/// Test defined by the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Test {
+    pub name: String,
+    pub body: Vec<Stack>,
+    /// Where the test was defined, if the program contains locations.
+    pub location: Option<Location>,
+}
+
+/// Result of a single test.
+#[derive(Debug)]
+pub struct Outcome {
+    pub name: String,
+    pub location: Option<Location>,
+    /// Error that stopped the test and where it happened, `None` if it passed.
+    pub failure: Option<(error::RuntimeError, Option<Location>)>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/**
+Runs the program, which defines the tests, then every test it defined.
+
+Each test starts with an empty stack, no locals and the variables the program left,
+so a failing test doesn't affect the others. The limits are counted from the start of every test. An error of the program itself is returned instead.
+*/
+pub fn run_tests(stack: Vec<Stack>, context: &mut Context) -> error::runtime::Result<Vec<Outcome>> {
+    run(stack, &mut Vec::new(), context)?;
+
+    let tests = std::mem::take(&mut context.tests);
+    let variables = context.variables.clone();
+
+    Ok(tests
+        .into_iter()
+        .map(|test| {
+            context.location = test.location.clone();
+            context.steps = 0;
+            context.started = None;
+            context.memory = 0;
+            context.memory_growth = 0;
+            let result = run(test.body, &mut Vec::new(), context);
+
+            let failure = result.err().map(|err| (err, context.location.clone()));
+            context.macro_frames.clear();
+            context.local_frames.clear();
+            context.variables = variables.clone();
+            context.tests.clear();
+
+            Outcome {
+                name: test.name,
+                location: test.location,
+                failure,
+            }
+        })
+        .collect())
+}
@@ -1,5 +1,11 @@
 use clap::*;
-use std::path::PathBuf;
+use std::{ops::RangeInclusive, path::PathBuf, time::Duration};
+
+use crate::{
+    debugger::trace,
+    runtime::{FsAccess, Limits},
+    util::host::Flush,
+};
 
 #[derive(Debug, Parser)]
 #[command(name = "stack_lang")]
@@ -9,16 +15,180 @@ struct Cli {
     command: Command,
 }
 
+/// Options of an executable built with `build --standalone`, the same as the ones of `run`.
+#[derive(Debug, Parser)]
+#[command(about = "Program built with stack_lang.", long_about = None)]
+struct StandaloneCli {
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+/// Flags of `run`, which executables built with `build --standalone` take too.
+#[derive(Debug, Args)]
+struct RunArgs {
+    #[command(flatten)]
+    fs: FsArgs,
+    /// Write the output in blocks instead of after every line, faster for programs printing a lot.
+    #[arg(long)]
+    buffer_output: bool,
+    /// Report how long reading, parsing and executing took.
+    #[arg(long, visible_alias = "verbose")]
+    time: bool,
+    /// Print the values left on the stack after the program finished.
+    #[arg(long)]
+    print_stack: bool,
+    #[command(flatten)]
+    limits: LimitArgs,
+    /// Write every operation with the stack before and after it to stderr.
+    #[arg(long)]
+    trace: bool,
+    /// Only trace the operations on these lines, e.g. 3-7.
+    #[arg(long, value_name = "LINES", requires = "trace", value_parser = parse_lines)]
+    trace_lines: Option<RangeInclusive<u32>>,
+    /// Only trace the operations inside of the scopes started on this line.
+    #[arg(long, value_name = "LINE", requires = "trace")]
+    trace_scope: Option<u32>,
+    /// Format of the trace.
+    #[arg(long, value_enum, requires = "trace", default_value_t = trace::Format::Text)]
+    trace_format: trace::Format,
+}
+
+impl RunArgs {
+    fn into_options(self) -> (FsAccess, RunOptions) {
+        let options = RunOptions {
+            flush: if self.buffer_output {
+                Flush::Block
+            } else {
+                Flush::Line
+            },
+            time: self.time,
+            print_stack: self.print_stack,
+            limits: self.limits.into_limits(),
+            trace: self.trace.then_some(trace::Options {
+                format: self.trace_format,
+                lines: self.trace_lines,
+                scope: self.trace_scope,
+            }),
+        };
+
+        (self.fs.into_fs_access(), options)
+    }
+}
+
+/// Flags of `run`, `test` and `debug` that decide what the file operations can touch.
+#[derive(Debug, Args)]
+struct FsArgs {
+    /// Allow file operations, optionally only inside of the given directory.
+    #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true)]
+    allow_fs: Option<Option<PathBuf>>,
+}
+
+impl FsArgs {
+    fn into_fs_access(self) -> FsAccess {
+        match self.allow_fs {
+            None => FsAccess::Denied,
+            Some(None) => FsAccess::Unrestricted,
+            Some(Some(root)) => FsAccess::Restricted(root),
+        }
+    }
+}
+
+/// Flags of `run` and `test` that stop the program with an error.
+#[derive(Debug, Args)]
+struct LimitArgs {
+    /// Stop the program after this many operations, values and loop iterations.
+    #[arg(long, value_name = "STEPS")]
+    max_steps: Option<u64>,
+    /// Stop the program when the stack holds more values than this.
+    #[arg(long, value_name = "VALUES")]
+    max_stack: Option<usize>,
+    /// Stop the program when the values on the stack take more memory than this, e.g. 64M.
+    #[arg(long, value_name = "BYTES", value_parser = parse_bytes)]
+    max_memory: Option<usize>,
+    /// Stop the program after this many seconds.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+}
+
+impl LimitArgs {
+    fn into_limits(self) -> Limits {
+        Limits {
+            max_steps: self.max_steps,
+            max_stack: self.max_stack,
+            max_memory: self.max_memory,
+            timeout: self.timeout,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Target {
+    /// Binary format ran with `run --bin`.
+    Binary,
+    /// x86-64 Linux assembly in GAS syntax.
+    #[value(name = "x86_64-asm")]
+    X86_64Asm,
+    /// Standalone C99 source file.
+    C,
+    /// WebAssembly module.
+    Wasm,
+    /// WebAssembly module in the text format.
+    Wat,
+}
+
+/// How `run` executes the program and what it reports besides the output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunOptions {
+    pub flush: Flush,
+    /// Report how long each phase took to stderr.
+    pub time: bool,
+    /// Print the values left on the stack after the program finished.
+    pub print_stack: bool,
+    pub limits: Limits,
+    /// Write every operation to stderr.
+    pub trace: Option<trace::Options>,
+}
+
 pub enum ExecutionMode {
     Run {
         path: PathBuf,
+        fs_access: FsAccess,
+        options: RunOptions,
     },
     RunBinary {
         path: PathBuf,
+        fs_access: FsAccess,
+        options: RunOptions,
+    },
+    RunEmbedded {
+        payload: Vec<u8>,
+        fs_access: FsAccess,
+        options: RunOptions,
     },
     Build {
         input_file: PathBuf,
         output_file: Option<PathBuf>,
+        strip: bool,
+        standalone: bool,
+        target: Target,
+    },
+    Disassemble {
+        path: PathBuf,
+        as_source: bool,
+    },
+    Check {
+        path: PathBuf,
+    },
+    Test {
+        paths: Vec<PathBuf>,
+        fs_access: FsAccess,
+        /// Counted for every test on its own.
+        limits: Limits,
+    },
+    Debug {
+        path: PathBuf,
+        fs_access: FsAccess,
+        breakpoints: Vec<u32>,
     },
 }
 
@@ -30,6 +200,8 @@ enum Command {
         /// Should the code be ran code in binary mode.
         #[arg(long)]
         bin: bool,
+        #[command(flatten)]
+        run: RunArgs,
         /// Path to code.
         #[arg(required = true)]
         path: PathBuf,
@@ -41,26 +213,164 @@ enum Command {
         // Path to output
         #[arg(long, short)]
         output: Option<PathBuf>,
+        /// Leave out debug information such as source locations.
+        #[arg(long)]
+        strip: bool,
+        /// Build an executable that runs without the interpreter.
+        #[arg(long, conflicts_with = "target")]
+        standalone: bool,
+        /// Format of the output.
+        #[arg(long, value_enum, default_value_t = Target::Binary)]
+        target: Target,
         /// Path to source code.
         #[arg(required = true)]
         input_file: PathBuf,
     },
+
+    /// Print the contents of a binary file.
+    #[command(arg_required_else_help = true)]
+    Disasm {
+        /// Print the program as equivalent source code instead of a listing.
+        #[arg(long)]
+        source: bool,
+        /// Path to binary file.
+        #[arg(required = true)]
+        path: PathBuf,
+    },
+
+    /// Find stack depth and type mistakes without running the code.
+    #[command(arg_required_else_help = true)]
+    Check {
+        /// Path to source code.
+        #[arg(required = true)]
+        path: PathBuf,
+    },
+
+    /// Run the tests defined with `test`.
+    Test {
+        #[command(flatten)]
+        fs: FsArgs,
+        #[command(flatten)]
+        limits: LimitArgs,
+        /// Test files, or directories searched for `*_test.sl` files.
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Run code step by step.
+    #[command(arg_required_else_help = true)]
+    Debug {
+        #[command(flatten)]
+        fs: FsArgs,
+        /// Stop at the first operation of the line, can be given more than once.
+        #[arg(long = "break", short, value_name = "LINE")]
+        breakpoints: Vec<u32>,
+        /// Path to source code.
+        #[arg(required = true)]
+        path: PathBuf,
+    },
 }
 
 pub fn get_execution_mode() -> ExecutionMode {
     let cli = Cli::parse();
 
     match cli.command {
-        Command::Run { bin, path } => {
+        Command::Run { bin, run, path } => {
+            let (fs_access, options) = run.into_options();
+
             if bin {
-                ExecutionMode::RunBinary { path }
+                ExecutionMode::RunBinary {
+                    path,
+                    fs_access,
+                    options,
+                }
             } else {
-                ExecutionMode::Run { path }
+                ExecutionMode::Run {
+                    path,
+                    fs_access,
+                    options,
+                }
             }
         }
-        Command::Build { output, input_file } => ExecutionMode::Build {
+        Command::Build {
+            output,
+            strip,
+            standalone,
+            target,
+            input_file,
+        } => ExecutionMode::Build {
             input_file,
             output_file: output,
+            strip,
+            standalone,
+            target,
+        },
+        Command::Disasm { source, path } => ExecutionMode::Disassemble {
+            path,
+            as_source: source,
+        },
+        Command::Check { path } => ExecutionMode::Check { path },
+        Command::Test { fs, limits, paths } => ExecutionMode::Test {
+            paths,
+            fs_access: fs.into_fs_access(),
+            limits: limits.into_limits(),
         },
+        Command::Debug {
+            fs,
+            breakpoints,
+            path,
+        } => ExecutionMode::Debug {
+            path,
+            fs_access: fs.into_fs_access(),
+            breakpoints,
+        },
+    }
+}
+
+/**
+Execution mode of an executable that carries its own program.
+*/
+pub fn get_standalone_mode(payload: Vec<u8>) -> ExecutionMode {
+    let (fs_access, options) = StandaloneCli::parse().run.into_options();
+
+    ExecutionMode::RunEmbedded {
+        payload,
+        fs_access,
+        options,
+    }
+}
+
+fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    let seconds: f64 = seconds.parse().map_err(|err| format!("{err}"))?;
+
+    Duration::try_from_secs_f64(seconds).map_err(|err| format!("{err}"))
+}
+
+/// A line like `3` or a range of lines like `3-7`.
+pub(crate) fn parse_lines(lines: &str) -> Result<RangeInclusive<u32>, String> {
+    let (first, last) = lines.split_once('-').unwrap_or((lines, lines));
+    let first: u32 = first.trim().parse().map_err(|err| format!("{err}"))?;
+    let last: u32 = last.trim().parse().map_err(|err| format!("{err}"))?;
+    if first > last {
+        return Err(format!(
+            "the range starts after it ends, try {last}-{first}"
+        ));
     }
+
+    Ok(first..=last)
+}
+
+/// Number of bytes, optionally with a `K`, `M` or `G` suffix for KiB, MiB or GiB.
+fn parse_bytes(bytes: &str) -> Result<usize, String> {
+    let (number, unit) = match bytes.char_indices().last() {
+        Some((index, 'K' | 'k')) => (&bytes[..index], 1 << 10),
+        Some((index, 'M' | 'm')) => (&bytes[..index], 1 << 20),
+        Some((index, 'G' | 'g')) => (&bytes[..index], 1 << 30),
+        _ => (bytes, 1),
+    };
+    let number: usize = number.parse().map_err(|err| format!("{err}"))?;
+
+    number
+        .checked_mul(unit)
+        .ok_or_else(|| "number too large to fit in target type".to_string())
 }
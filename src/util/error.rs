@@ -8,6 +8,15 @@ pub enum ParsingError {
     KeywordError { reason: String },
     #[error("Parsing Error\n\tExpected {expected} but got {got}.")]
     MismatchedTypes { expected: String, got: String },
+    #[error("\tFailed to import `{path}`.\n\t{reason}")]
+    ImportFailed { path: String, reason: String },
+    #[error("\tIn `{path}` at {line}:{column}:\n{error}")]
+    InImport {
+        path: String,
+        line: usize,
+        column: usize,
+        error: Box<ParsingError>,
+    },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -23,6 +32,158 @@ pub enum RuntimeError {
     },
     #[error("\tExpected {expected} but got {got}.")]
     MismatchedTypes { expected: String, got: String },
+    #[error("\tFile system access to `{path}` is not allowed.\n\t{reason}")]
+    FsAccessDenied { path: String, reason: String },
+    #[error("\tFile system operation on `{path}` failed.\n\t{reason}")]
+    FsOperationFailed { path: String, reason: String },
+    #[error("\tLocal `{name}` isn't bound in this scope or the scopes running it.")]
+    UndefinedLocal { name: String },
+    #[error("\tVariable `{name}` is read before it's set.")]
+    UndefinedVariable { name: String },
+    #[error("\tMacro `{name}` takes {needed} value(s) but the stack has {got}.\n\tDeclared stack effect: {effect}")]
+    MacroArguments {
+        name: String,
+        effect: String,
+        needed: usize,
+        got: usize,
+    },
+    #[error("\tMacro `{name}` should change the stack depth by {expected} but changed it by {got}.\n\tDeclared stack effect: {effect}")]
+    MacroResults {
+        name: String,
+        effect: String,
+        expected: i64,
+        got: i64,
+    },
+    #[error("\t{kind}: {message}")]
+    Thrown { kind: String, message: String },
+    #[error("\tAssertion failed.\n\t{reason}")]
+    AssertionFailed { reason: String },
+    #[error("\tCouldn't write the output.\n\t{reason}")]
+    OutputFailed { reason: String },
+    #[error("\tThe program didn't finish in {steps} steps.")]
+    StepLimitExceeded { steps: u64 },
+    #[error("\tThe stack grew past {limit} values after {steps} steps.")]
+    StackLimitExceeded { limit: usize, steps: u64 },
+    #[error("\tThe values on the stack took {used} bytes, more than the limit of {limit} bytes, after {steps} steps.")]
+    MemoryLimitExceeded {
+        limit: usize,
+        used: usize,
+        steps: u64,
+    },
+    #[error("\tThe program didn't finish in {timeout:?}, it stopped after {steps} steps.")]
+    Timeout {
+        timeout: std::time::Duration,
+        steps: u64,
+    },
+    #[error("\tThe program was stopped after {steps} steps.")]
+    Stopped { steps: u64 },
+}
+
+impl RuntimeError {
+    /// Name of the error that `try` gives to the handler, the one passed to `throw` for thrown errors.
+    pub fn kind(&self) -> &str {
+        match self {
+            RuntimeError::InvalidOperation { .. } => "InvalidOperation",
+            RuntimeError::InsufficientArguments { .. } => "InsufficientArguments",
+            RuntimeError::MismatchedTypes { .. } => "MismatchedTypes",
+            RuntimeError::FsAccessDenied { .. } => "FsAccessDenied",
+            RuntimeError::FsOperationFailed { .. } => "FsOperationFailed",
+            RuntimeError::UndefinedLocal { .. } => "UndefinedLocal",
+            RuntimeError::UndefinedVariable { .. } => "UndefinedVariable",
+            RuntimeError::MacroArguments { .. } => "MacroArguments",
+            RuntimeError::MacroResults { .. } => "MacroResults",
+            RuntimeError::AssertionFailed { .. } => "AssertionFailed",
+            RuntimeError::OutputFailed { .. } => "OutputFailed",
+            RuntimeError::StepLimitExceeded { .. } => "StepLimitExceeded",
+            RuntimeError::StackLimitExceeded { .. } => "StackLimitExceeded",
+            RuntimeError::MemoryLimitExceeded { .. } => "MemoryLimitExceeded",
+            RuntimeError::Timeout { .. } => "Timeout",
+            RuntimeError::Stopped { .. } => "Stopped",
+            RuntimeError::Thrown { kind, .. } => kind,
+        }
+    }
+
+    /// Description of the error without the indentation used when it's printed.
+    pub fn message(&self) -> String {
+        match self {
+            RuntimeError::Thrown { message, .. } => message.clone(),
+            _ => self
+                .to_string()
+                .lines()
+                .map(|line| line.trim_start_matches('\t'))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /**
+    Errors of the execution limits and the ones stopping the program from the outside.
+    `try` can't catch them, so a program can't ignore its limits.
+    */
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            RuntimeError::StepLimitExceeded { .. }
+                | RuntimeError::StackLimitExceeded { .. }
+                | RuntimeError::MemoryLimitExceeded { .. }
+                | RuntimeError::Timeout { .. }
+                | RuntimeError::Stopped { .. }
+        )
+    }
+
+    /// Values that `try` pushes for the handler, the kind below the message.
+    pub fn to_values(&self) -> [crate::ValueType; 2] {
+        [
+            crate::ValueType::Text(self.kind().to_string()),
+            crate::ValueType::Text(self.message()),
+        ]
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum BinaryError {
+    #[error("\tNot a stack_lang binary file (invalid magic number).")]
+    InvalidMagic,
+    #[error("\tUnsupported binary format version {found} (built with stack_lang {compiler}), this interpreter supports version {supported}.\n\tRebuild the program from source.")]
+    UnsupportedVersion {
+        found: u16,
+        supported: u16,
+        compiler: String,
+    },
+    #[error("\tBinary file is corrupted.\n\t{reason}")]
+    Corrupted { reason: String },
+    #[error("\tCouldn't encode the program.\n\t{reason}")]
+    Encoding { reason: String },
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum CodegenError {
+    #[error("\t{what} is not supported by the `{target}` target.")]
+    Unsupported { target: String, what: String },
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum CheckError {
+    #[error("\t`{operation}` needs {needed} value(s) but the stack can hold only {got}.")]
+    Underflow {
+        operation: String,
+        needed: usize,
+        got: usize,
+    },
+    #[error("\t`{operation}` expected {expected} but got {got}.")]
+    MismatchedTypes {
+        operation: String,
+        expected: String,
+        got: String,
+    },
+    #[error("\tThe body of `{operation}` changes the stack depth by {change} on every iteration, but the number of iterations isn't known.")]
+    UnpredictableLoop { operation: String, change: i64 },
+    #[error("\tThe body leaves {got} value(s) but the declared stack effect {effect} leaves {expected}.")]
+    EffectMismatch {
+        effect: String,
+        expected: usize,
+        got: usize,
+    },
 }
 
 pub mod parsing {
@@ -32,3 +193,11 @@ pub mod parsing {
 pub mod runtime {
     pub type Result<T> = std::result::Result<T, super::RuntimeError>;
 }
+
+pub mod binary {
+    pub type Result<T> = std::result::Result<T, super::BinaryError>;
+}
+
+pub mod codegen {
+    pub type Result<T> = std::result::Result<T, super::CodegenError>;
+}
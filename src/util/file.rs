@@ -1,11 +1,143 @@
 use std::{
     fs,
+    io::{self, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
 };
 
+use super::error::{self, BinaryError};
+
+/// First bytes of every binary file.
+pub const MAGIC: [u8; 4] = *b"STKL";
+/**
+Version of the binary layout.
+
+Bump it whenever `Stack`, `ValueType` or `OperationType` change in a way that alters their encoding.
+*/
+pub const FORMAT_VERSION: u16 = 4;
+pub const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+/**
+Last bytes of a standalone executable.
+
+It is preceded by the length of the program as u64 little endian, which is preceded by the program itself.
+*/
+pub const STANDALONE_MAGIC: [u8; 8] = *b"STKLEXE\0";
+
+/**
+Layout of the binary file:
+```text
+magic            4 bytes  "STKL"
+format version   u16      little endian
+compiler version u8 length followed by UTF-8 text
+checksum         u32      little endian CRC-32 of the payload
+payload          postcard encoded `Binary`
+```
+*/
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Binary {
     pub stack: Vec<crate::Stack>,
+    /// Left out when built with `--strip`, the stack doesn't contain locations then.
+    pub debug: Option<DebugInfo>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct DebugInfo {
+    pub source_file: String,
+}
+
+impl Binary {
+    pub fn to_bytes(&self) -> error::binary::Result<Vec<u8>> {
+        let payload = postcard::to_allocvec(self).map_err(|err| BinaryError::Encoding {
+            reason: err.to_string(),
+        })?;
+
+        let mut bytes = Vec::with_capacity(payload.len() + 16 + COMPILER_VERSION.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.push(COMPILER_VERSION.len() as u8);
+        bytes.extend_from_slice(COMPILER_VERSION.as_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> error::binary::Result<Self> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(BinaryError::InvalidMagic);
+        }
+
+        let version = u16::from_le_bytes(reader.take_array()?);
+        let compiler_len = reader.take(1)?[0] as usize;
+        let compiler = String::from_utf8_lossy(reader.take(compiler_len)?).to_string();
+
+        if version != FORMAT_VERSION {
+            return Err(BinaryError::UnsupportedVersion {
+                found: version,
+                supported: FORMAT_VERSION,
+                compiler,
+            });
+        }
+
+        let checksum = u32::from_le_bytes(reader.take_array()?);
+        let payload = reader.rest();
+
+        if crc32(payload) != checksum {
+            return Err(BinaryError::Corrupted {
+                reason: "Checksum does not match the contents.".to_string(),
+            });
+        }
+
+        postcard::from_bytes(payload).map_err(|err| BinaryError::Corrupted {
+            reason: err.to_string(),
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, amount: usize) -> error::binary::Result<&'a [u8]> {
+        if self.position + amount > self.bytes.len() {
+            return Err(BinaryError::Corrupted {
+                reason: "Unexpected end of the header.".to_string(),
+            });
+        }
+
+        let taken = &self.bytes[self.position..self.position + amount];
+        self.position += amount;
+
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> error::binary::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+}
+
+/**
+CRC-32 (IEEE) checksum, computed bit by bit as binaries are small.
+*/
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
 }
 
 pub fn extract_text(path: &PathBuf) -> String {
@@ -14,12 +146,112 @@ pub fn extract_text(path: &PathBuf) -> String {
     fs::read_to_string(path).expect("Could not read the file.\n")
 }
 
-pub fn extract_bin(path: &PathBuf) -> Vec<u8> {
-    let path = Path::new(&path);
+/**
+Files given directly and the `*_test.sl` files anywhere inside of the given directories, sorted.
+
+Hidden directories and `target` directories inside of them are skipped.
+*/
+pub fn find_tests(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    fn walk(directory: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+        let entries = fs::read_dir(directory)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", directory.display())))?;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if !name.starts_with('.') && name != "target" {
+                    walk(&path, found)?;
+                }
+            } else if path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().ends_with("_test.sl"))
+            {
+                found.push(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut found = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk(path, &mut found)?;
+        } else {
+            found.push(path.clone());
+        }
+    }
+    found.sort();
+    found.dedup();
+
+    Ok(found)
+}
+
+pub fn extract_bin(path: &PathBuf) -> io::Result<Vec<u8>> {
+    fs::read(path)
+}
 
-    fs::read(path).expect("Could not read the file.\n")
+pub fn write_bin(content: Vec<u8>, path: &PathBuf) -> io::Result<()> {
+    fs::write(path, content)
 }
 
-pub fn write_bin(content: Vec<u8>, path: &PathBuf) {
-    fs::write(path, content).expect("Could not write to output file.");
+/**
+Gives the program appended to the executable by `build --standalone`, if there is one.
+*/
+pub fn extract_embedded(executable: &Path) -> Option<Vec<u8>> {
+    let mut file = fs::File::open(executable).ok()?;
+    let trailer_len = (STANDALONE_MAGIC.len() + 8) as u64;
+    let file_len = file.metadata().ok()?.len();
+    if file_len < trailer_len {
+        return None;
+    }
+
+    let mut trailer = [0; 16];
+    file.seek(SeekFrom::End(-(trailer_len as i64))).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+    if trailer[8..] != STANDALONE_MAGIC {
+        return None;
+    }
+
+    let payload_len = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    if payload_len > file_len - trailer_len {
+        return None;
+    }
+
+    let mut payload = vec![0; payload_len as usize];
+    file.seek(SeekFrom::End(-((trailer_len + payload_len) as i64)))
+        .ok()?;
+    file.read_exact(&mut payload).ok()?;
+
+    Some(payload)
+}
+
+/**
+Writes a copy of the interpreter with the program appended to it.
+*/
+pub fn write_standalone(interpreter: &Path, content: Vec<u8>, path: &PathBuf) -> io::Result<()> {
+    let mut executable = fs::read(interpreter)?;
+
+    // The interpreter can be a standalone executable itself.
+    if let Some(embedded) = extract_embedded(interpreter) {
+        executable.truncate(executable.len() - embedded.len() - STANDALONE_MAGIC.len() - 8);
+    }
+
+    let content_len = content.len() as u64;
+    executable.extend(content);
+    executable.extend_from_slice(&content_len.to_le_bytes());
+    executable.extend_from_slice(&STANDALONE_MAGIC);
+
+    write_bin(executable, path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
 }
@@ -0,0 +1,145 @@
+use std::{
+    cell::RefCell,
+    fmt::Debug,
+    io::{self, BufWriter, Write},
+    ops::Deref,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/**
+Everything the interpreter needs from the environment it runs in.
+
+Keeping it behind a trait lets the interpreter run where there is no process stdout or clock,
+like `wasm32-unknown-unknown`.
+*/
+pub trait Host: Debug {
+    /// Receives the output of the program.
+    fn stdout(&mut self) -> &mut dyn Write;
+
+    /// Receives messages about the program that aren't its output, like how long it took.
+    fn stderr(&mut self) -> &mut dyn Write;
+
+    /// Time elapsed since a fixed point, `None` if the host has no clock.
+    fn now(&self) -> Option<Duration>;
+
+    /// Writes output of the program.
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        self.stdout().write_all(text.as_bytes())
+    }
+}
+
+/// When the output written to `StdHost` reaches the process stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Flush {
+    /// After every line, so the output shows up as soon as it's printed.
+    #[default]
+    Line,
+    /// When the buffer fills up or the host is dropped, faster for programs printing a lot.
+    Block,
+}
+
+/// Writes to the stdout and stderr of the process and measures time with `Instant`.
+pub struct StdHost {
+    started: Instant,
+    stdout: Box<dyn Write>,
+    stderr: io::Stderr,
+}
+
+impl StdHost {
+    pub fn new(flush: Flush) -> Self {
+        StdHost {
+            started: Instant::now(),
+            stdout: match flush {
+                // `Stdout` is already line buffered.
+                Flush::Line => Box::new(io::stdout()),
+                Flush::Block => Box::new(BufWriter::new(io::stdout().lock())),
+            },
+            stderr: io::stderr(),
+        }
+    }
+}
+
+impl Default for StdHost {
+    fn default() -> Self {
+        StdHost::new(Flush::default())
+    }
+}
+
+impl Debug for StdHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StdHost")
+            .field("started", &self.started)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Host for StdHost {
+    fn stdout(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+
+    fn stderr(&mut self) -> &mut dyn Write {
+        &mut self.stderr
+    }
+
+    fn now(&self) -> Option<Duration> {
+        Some(self.started.elapsed())
+    }
+}
+
+/// Text written to it, shared between the clones so it can be read after the writer was handed over.
+#[derive(Debug, Default, Clone)]
+pub struct SharedBuffer(Rc<RefCell<String>>);
+
+impl Deref for SharedBuffer {
+    type Target = RefCell<String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().push_str(&String::from_utf8_lossy(buf));
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Collects the output in memory, it only has a clock when made with `with_clock`.
+#[derive(Debug, Default, Clone)]
+pub struct BufferedHost {
+    pub stdout: SharedBuffer,
+    pub stderr: SharedBuffer,
+    started: Option<Instant>,
+}
+
+impl BufferedHost {
+    /// Measures time with `Instant`, which panics on targets without a clock like `wasm32-unknown-unknown`.
+    pub fn with_clock() -> Self {
+        BufferedHost {
+            started: Some(Instant::now()),
+            ..Default::default()
+        }
+    }
+}
+
+impl Host for BufferedHost {
+    fn stdout(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+
+    fn stderr(&mut self) -> &mut dyn Write {
+        &mut self.stderr
+    }
+
+    fn now(&self) -> Option<Duration> {
+        self.started.map(|started| started.elapsed())
+    }
+}
@@ -1,30 +1,29 @@
 pub mod cli;
 pub mod error;
 pub mod file;
+pub mod host;
 pub mod parsing;
+pub mod timing;
 #[cfg(test)]
 mod util_test;
 
 /**
-Macro that prints the amount of time that it takes to run the expression only if run in debug mode.
+Macro that records how long it takes to run the expression as the given phase of `timings`,
+if the host has a clock.
 
 Macro returns the value of expression.
 */
 #[macro_export]
-macro_rules! log_debug_time {
-    ( $function:expr, $what:expr ) => {
-        if
-        /*cfg!(debug_assertions)*/
-        true {
-            use std::time::Instant;
+macro_rules! time_phase {
+    ( $timings:expr, $host:expr, $phase:expr, $function:expr ) => {{
+        use $crate::util::host::Host as _;
 
-            let now = Instant::now();
-            let result = $function;
-            println!("{} took {:?}", $what, now.elapsed());
-
-            result
-        } else {
-            $function
+        let start = $host.now();
+        let result = $function;
+        if let (Some(start), Some(end)) = (start, $host.now()) {
+            $timings.record($phase, end - start);
         }
-    };
+
+        result
+    }};
 }
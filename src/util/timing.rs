@@ -0,0 +1,61 @@
+use std::{fmt, time::Duration};
+
+/// Part of a run measured by `Timings`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Reading the source or the binary file.
+    Read,
+    /// Turning the source into the program.
+    Parse,
+    /// Decoding the program from the binary format.
+    Deserialize,
+    /// Running the program.
+    Execute,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Read => "read",
+            Phase::Parse => "parse",
+            Phase::Deserialize => "deserialize",
+            Phase::Execute => "execute",
+        })
+    }
+}
+
+/// How long the phases of a run took, in the order they ran.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Timings {
+    pub phases: Vec<(Phase, Duration)>,
+}
+
+impl Timings {
+    pub fn record(&mut self, phase: Phase, duration: Duration) {
+        self.phases.push((phase, duration));
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, duration)| *duration).sum()
+    }
+}
+
+/**
+Table with a row for every phase followed by the total.
+
+```text
+read        12.5µs
+parse       103.2µs
+execute     1.2ms
+total       1.3157ms
+```
+*/
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (phase, duration) in &self.phases {
+            writeln!(f, "{:<12}{duration:?}", phase.to_string())?;
+        }
+
+        write!(f, "{:<12}{:?}", "total", self.total())
+    }
+}
@@ -1,18 +1,54 @@
-use crate::{log_debug_time, util::parsing::find_closing_bracket};
+use crate::{
+    time_phase,
+    util::{
+        cli,
+        error::BinaryError,
+        file::{self, Binary},
+        host,
+        parsing::find_closing_bracket,
+        timing::{Phase, Timings},
+    },
+    OperationType, Stack, ValueType,
+};
 
 #[test]
-fn test_log_debug_macro() {
+fn test_time_phase_macro() {
     fn expensive_operation() -> u8 {
-        use std::thread::sleep;
-
-        sleep(std::time::Duration::from_millis(3000));
+        std::thread::sleep(std::time::Duration::from_millis(30));
 
         5
     }
 
-    let result = log_debug_time!(expensive_operation(), "Expensive operation");
+    let mut timings = Timings::default();
+    let host = host::StdHost::default();
+    let result = time_phase!(timings, host, Phase::Execute, expensive_operation());
 
     assert_eq!(5, result);
+    assert_eq!(timings.phases.len(), 1);
+    assert_eq!(timings.phases[0].0, Phase::Execute);
+    assert!(timings.phases[0].1 >= std::time::Duration::from_millis(30));
+
+    // Without a clock there is nothing to record.
+    let mut timings = Timings::default();
+    let host = host::BufferedHost::default();
+    time_phase!(timings, host, Phase::Parse, ());
+
+    assert_eq!(timings, Timings::default());
+}
+
+#[test]
+fn test_timings_report() {
+    use std::time::Duration;
+
+    let mut timings = Timings::default();
+    timings.record(Phase::Read, Duration::from_micros(5));
+    timings.record(Phase::Deserialize, Duration::from_micros(20));
+    timings.record(Phase::Execute, Duration::from_millis(2));
+
+    assert_eq!(
+        timings.to_string(),
+        "read        5µs\ndeserialize 20µs\nexecute     2ms\ntotal       2.025ms"
+    );
 }
 
 #[test]
@@ -23,3 +59,142 @@ fn test_find_closing_bracket() {
 
     println!("{}", &src[1..31]);
 }
+
+fn sample_binary() -> Binary {
+    Binary {
+        stack: vec![
+            Stack::Value(ValueType::Int(2)),
+            Stack::Value(ValueType::Text("Hell'o".to_string())),
+            Stack::Operation(OperationType::Println),
+        ],
+        debug: None,
+    }
+}
+
+#[test]
+fn test_binary_round_trip() {
+    let bytes = sample_binary().to_bytes().unwrap();
+
+    assert_eq!(&bytes[..4], &file::MAGIC);
+    assert_eq!(
+        Binary::from_bytes(&bytes).unwrap().stack,
+        sample_binary().stack
+    );
+}
+
+#[test]
+fn test_binary_invalid_magic() {
+    let bytes = postcard::to_allocvec(&sample_binary()).unwrap();
+
+    assert_eq!(
+        Binary::from_bytes(&bytes).err(),
+        Some(BinaryError::InvalidMagic)
+    );
+}
+
+#[test]
+fn test_binary_version_mismatch() {
+    let mut bytes = sample_binary().to_bytes().unwrap();
+    bytes[4..6].copy_from_slice(&(file::FORMAT_VERSION + 1).to_le_bytes());
+
+    assert_eq!(
+        Binary::from_bytes(&bytes).err(),
+        Some(BinaryError::UnsupportedVersion {
+            found: file::FORMAT_VERSION + 1,
+            supported: file::FORMAT_VERSION,
+            compiler: file::COMPILER_VERSION.to_string(),
+        })
+    );
+}
+
+#[test]
+fn test_binary_corrupted() {
+    let mut bytes = sample_binary().to_bytes().unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+
+    assert!(matches!(
+        Binary::from_bytes(&bytes),
+        Err(BinaryError::Corrupted { .. })
+    ));
+
+    let truncated = &sample_binary().to_bytes().unwrap()[..6];
+
+    assert!(matches!(
+        Binary::from_bytes(truncated),
+        Err(BinaryError::Corrupted { .. })
+    ));
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(file::crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn test_standalone_round_trip() {
+    // Unique to the process, so runs at the same time don't share the directory.
+    let dir = std::env::temp_dir().join(format!(
+        "stack_lang_test_standalone_round_trip_{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let interpreter = dir.join("interpreter");
+    let executable = dir.join("program");
+    let program = sample_binary().to_bytes().unwrap();
+
+    std::fs::write(&interpreter, b"not really an interpreter").unwrap();
+    assert_eq!(file::extract_embedded(&interpreter), None);
+
+    file::write_standalone(&interpreter, program.clone(), &executable).unwrap();
+    assert_eq!(file::extract_embedded(&executable), Some(program.clone()));
+
+    // Building from a standalone executable replaces the program instead of appending another one.
+    let rebuilt = dir.join("rebuilt");
+    file::write_standalone(&executable, b"other".to_vec(), &rebuilt).unwrap();
+    assert_eq!(file::extract_embedded(&rebuilt), Some(b"other".to_vec()));
+    assert_eq!(
+        std::fs::metadata(&rebuilt).unwrap().len(),
+        std::fs::metadata(&interpreter).unwrap().len() + 5 + 16
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_find_tests() {
+    let dir =
+        std::env::temp_dir().join(format!("stack_lang_test_find_tests_{}", std::process::id()));
+    for directory in ["lib", ".git", "target/debug"] {
+        std::fs::create_dir_all(dir.join(directory)).unwrap();
+    }
+    for file in [
+        "a_test.sl",
+        "lib/b_test.sl",
+        "lib/b.sl",
+        ".git/c_test.sl",
+        "target/debug/d_test.sl",
+    ] {
+        std::fs::write(dir.join(file), "").unwrap();
+    }
+
+    assert_eq!(
+        file::find_tests(&[dir.clone(), dir.join("lib/b.sl")]).unwrap(),
+        vec![
+            dir.join("a_test.sl"),
+            dir.join("lib/b.sl"),
+            dir.join("lib/b_test.sl")
+        ]
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_parse_lines() {
+    assert_eq!(cli::parse_lines("3"), Ok(3..=3));
+    assert_eq!(cli::parse_lines("3-7"), Ok(3..=7));
+    assert_eq!(cli::parse_lines(" 3 - 7 "), Ok(3..=7));
+    assert!(cli::parse_lines("7-3").is_err());
+    assert!(cli::parse_lines("3-").is_err());
+}
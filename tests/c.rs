@@ -0,0 +1,51 @@
+mod common;
+
+use common::*;
+use std::process::Command;
+
+#[test]
+fn test_examples_match_interpreter() {
+    if !is_installed("cc") {
+        eprintln!("Skipping, `cc` is needed to compile the output.");
+        return;
+    }
+
+    let dir = temp_dir("c");
+
+    for example in examples() {
+        let name = example.file_stem().unwrap().to_str().unwrap();
+        let source = dir.join(format!("{name}.c"));
+        let executable = dir.join(name);
+
+        let build = stack_lang(&[
+            "build",
+            "--target",
+            "c",
+            example.to_str().unwrap(),
+            "-o",
+            source.to_str().unwrap(),
+        ]);
+        assert!(build.status.success(), "{name} failed to transpile");
+
+        let compiled = Command::new("cc")
+            .args(["-std=c99", "-Wall", "-Werror"])
+            .arg(&source)
+            .arg("-o")
+            .arg(&executable)
+            .arg("-lm")
+            .status()
+            .unwrap();
+        assert!(compiled.success(), "{name} failed to compile");
+
+        let output = Command::new(&executable).output().unwrap();
+        assert!(output.status.success(), "{name} failed at runtime");
+
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            interpreter_output(&example),
+            "{name} prints something else than in the interpreter"
+        );
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
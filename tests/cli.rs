@@ -0,0 +1,182 @@
+mod common;
+
+use common::*;
+
+fn write_program(name: &str, src: &str) -> String {
+    let path = temp_dir("cli").join(format!("{name}.sl"));
+    std::fs::write(&path, src).unwrap();
+
+    path.display().to_string()
+}
+
+#[test]
+fn test_run_prints_only_the_output() {
+    let path = write_program("only_output", "1 2 + println 4");
+    let output = stack_lang(&["run", &path]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+}
+
+#[test]
+fn test_run_print_stack() {
+    let path = write_program("print_stack", "1 2 + println 4");
+    let output = stack_lang(&["run", "--print-stack", &path]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "3\n[Int(3), Int(4)]\n"
+    );
+}
+
+#[test]
+fn test_run_time() {
+    let path = write_program("time", "1 2 + println");
+
+    let output = stack_lang(&["run", "--time", &path]);
+    let report = String::from_utf8(output.stderr).unwrap();
+    let phases: Vec<&str> = report
+        .lines()
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+    assert_eq!(phases, ["read", "parse", "execute", "total"]);
+
+    let binary = temp_dir("cli").join("time.bin");
+    let build = stack_lang(&["build", &path, "-o", binary.to_str().unwrap()]);
+    assert!(build.status.success());
+
+    let output = stack_lang(&["run", "--bin", "--verbose", binary.to_str().unwrap()]);
+    let report = String::from_utf8(output.stderr).unwrap();
+    let phases: Vec<&str> = report
+        .lines()
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect();
+
+    assert_eq!(phases, ["read", "deserialize", "execute", "total"]);
+}
+
+#[test]
+fn test_run_limits() {
+    let path = write_program("limits", "\"looping\" println { } true while");
+
+    let output = stack_lang(&["run", "--max-steps", "1000", &path]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!output.status.success());
+    assert!(stdout.starts_with("looping\n"));
+    assert!(stdout.contains("The program didn't finish in 1000 steps."));
+
+    let output = stack_lang(&["run", "--timeout", "0.05", &path]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("The program didn't finish in 50ms"));
+
+    let path = write_program("memory", "\"text\" { copy } 1000000 for");
+    let output = stack_lang(&["run", "--max-memory", "1K", &path]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("more than the limit of 1024 bytes"));
+}
+
+#[test]
+fn test_run_trace() {
+    let path = write_program("trace", "1 2 + println\n{ 3 * } 2 for");
+
+    let output = stack_lang(&["run", "--trace", "--trace-lines", "2-2", &path]);
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap().lines().count(), 4);
+
+    let output = stack_lang(&["run", "--trace", "--trace-format", "json", &path]);
+    let trace = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(trace.lines().count(), 10);
+    assert!(trace
+        .lines()
+        .all(|line| line.starts_with("{\"event\":") && line.ends_with('}')));
+
+    let output = stack_lang(&["run", "--trace", "--trace-lines", "7-3", &path]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("the range starts after it ends, try 3-7"));
+
+    // The trace options only work together with `--trace`.
+    assert!(!stack_lang(&["run", "--trace-scope", "2", &path])
+        .status
+        .success());
+}
+
+#[test]
+fn test_standalone_run_flags() {
+    let path = write_program("standalone", "\"notes.txt\" file_exists println");
+    let executable = temp_dir("cli").join("standalone");
+    let build = stack_lang(&[
+        "build",
+        "--standalone",
+        &path,
+        "-o",
+        executable.to_str().unwrap(),
+    ]);
+    assert!(build.status.success());
+    let run = |args: &[&str]| {
+        std::process::Command::new(&executable)
+            .args(args)
+            .current_dir(temp_dir("cli"))
+            .output()
+            .unwrap()
+    };
+
+    assert!(!run(&[]).status.success());
+    assert_eq!(
+        String::from_utf8(run(&["--allow-fs=.", "--buffer-output"]).stdout).unwrap(),
+        "false\n"
+    );
+
+    let output = run(&["--allow-fs=.", "--print-stack", "--time"]);
+    let phases: Vec<String> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .map(|line| line.split_whitespace().next().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "false\n[Bool(false)]\n"
+    );
+    assert_eq!(phases, ["deserialize", "execute", "total"]);
+
+    let output = run(&["--allow-fs=.", "--max-steps", "2"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("The program didn't finish in 2 steps."));
+
+    let output = run(&["--allow-fs=.", "--max-memory", "1"]);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("more than the limit of 1 bytes"));
+
+    let output = run(&["--allow-fs=.", "--trace"]);
+    assert_eq!(String::from_utf8(output.stderr).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn test_missing_binary() {
+    let path = temp_dir("cli").join("missing.bin");
+    let output = stack_lang(&["run", "--bin", path.to_str().unwrap()]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains(&format!("Could not read {}", path.display())));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+}
@@ -0,0 +1,60 @@
+// Every test binary includes this module but only uses some of the helpers.
+#![allow(dead_code)]
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+pub fn stack_lang(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_stack_lang_compiler"))
+        .args(args)
+        .output()
+        .expect("Could not start stack_lang.")
+}
+
+pub fn stack_lang_in(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_stack_lang_compiler"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Could not start stack_lang.")
+}
+
+/// Output of the program ran by the interpreter.
+pub fn interpreter_output(path: &Path) -> String {
+    let output = stack_lang(&["run", path.to_str().unwrap()]);
+    assert!(
+        output.status.success(),
+        "{path:?} failed in the interpreter"
+    );
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+pub fn examples() -> Vec<PathBuf> {
+    examples_in("examples")
+}
+
+pub fn examples_in(dir: &str) -> Vec<PathBuf> {
+    let mut examples: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "sl"))
+        .collect();
+    examples.sort();
+
+    examples
+}
+
+pub fn is_installed(program: &str) -> bool {
+    Command::new(program).arg("--version").output().is_ok()
+}
+
+/// A directory for the test, unique to the process so runs at the same time don't share it.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("stack_lang_{name}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
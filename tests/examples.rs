@@ -0,0 +1,156 @@
+/*!
+Golden tests of the examples: their output is compared with the `.expected` file next to them.
+The examples in `examples/fs` use the file system and are only run by the interpreter.
+
+Run with `BLESS=1` to write the current output to the `.expected` files and rebuild `examples_compiled`
+instead of comparing.
+*/
+mod common;
+
+use common::*;
+use stack_lang_compiler::{
+    ast, runtime,
+    util::{file, host::BufferedHost},
+    Stack,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+fn bless() -> bool {
+    std::env::var_os("BLESS").is_some()
+}
+
+fn parse(path: &Path) -> Vec<Stack> {
+    let src = std::fs::read_to_string(path).unwrap();
+
+    let mut stack = Vec::new();
+    ast::fill_with_imports(
+        &src,
+        &mut stack,
+        &mut 1,
+        &mut 1,
+        &mut HashMap::new(),
+        &mut ast::Imports::new(path.to_path_buf()),
+    )
+    .unwrap_or_else(|err| panic!("{path:?} failed to parse.\n{err}"));
+
+    stack
+}
+
+/// Same binary as `build` produces from the example.
+fn build(path: &Path) -> Vec<u8> {
+    file::Binary {
+        stack: parse(path),
+        debug: Some(file::DebugInfo {
+            source_file: path.display().to_string(),
+        }),
+    }
+    .to_bytes()
+    .unwrap()
+}
+
+/// Runs the program with everything it prints captured.
+fn output(stack: Vec<Stack>, name: &str) -> String {
+    let host = BufferedHost::default();
+    let mut context = runtime::Context {
+        host: Box::new(host.clone()),
+        ..Default::default()
+    };
+
+    if let Err(err) = runtime::run(stack, &mut Vec::new(), &mut context) {
+        panic!("{name} failed at {:?}.\n{err}", context.location);
+    }
+
+    host.stdout.take()
+}
+
+fn compiled(example: &Path) -> PathBuf {
+    Path::new("examples_compiled").join(example.with_extension("bin").file_name().unwrap())
+}
+
+/// Compares the output with the `.expected` file, or replaces the file with it when blessing.
+fn assert_expected(example: &Path, output: &str, path_taken: &str) {
+    let expected_file = example.with_extension("expected");
+
+    if bless() {
+        std::fs::write(&expected_file, output).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&expected_file).unwrap_or_else(|_| {
+        panic!("{expected_file:?} is missing, create it with `BLESS=1 cargo test --test examples`.")
+    });
+    assert!(
+        output == expected,
+        "Output of {example:?} ran from {path_taken} differs from {expected_file:?}.\n--- expected\n{expected}\n--- got\n{output}"
+    );
+}
+
+#[test]
+fn test_examples_from_source() {
+    for example in examples() {
+        let name = example.display().to_string();
+
+        assert_expected(&example, &output(parse(&example), &name), "source");
+    }
+}
+
+#[test]
+fn test_examples_from_binary() {
+    for example in examples() {
+        let name = example.display().to_string();
+        let bin = file::Binary::from_bytes(&build(&example)).unwrap();
+
+        // Blessing is left to the test from source, both have to print the same.
+        if !bless() {
+            assert_expected(&example, &output(bin.stack, &name), "binary");
+        }
+    }
+}
+
+/// File system examples run with access only to an empty directory, which is their working directory.
+#[test]
+fn test_fs_examples() {
+    for example in examples_in("examples/fs") {
+        let dir = std::env::temp_dir().join(format!(
+            "stack_lang_fs_example_{}_{}",
+            std::process::id(),
+            example.file_stem().unwrap().to_str().unwrap()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = example.canonicalize().unwrap();
+        let output = stack_lang_in(&dir, &["run", "--allow-fs=.", path.to_str().unwrap()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(output.status.success(), "{example:?} failed.");
+        assert_expected(
+            &example,
+            &String::from_utf8(output.stdout).unwrap(),
+            "source",
+        );
+    }
+}
+
+#[test]
+fn test_compiled_examples_are_up_to_date() {
+    for example in examples() {
+        let compiled = compiled(&example);
+
+        if bless() {
+            std::fs::write(&compiled, build(&example)).unwrap();
+            continue;
+        }
+
+        // The header and debug info name the compiler and the source path, only the program is compared.
+        let committed = std::fs::read(&compiled)
+            .ok()
+            .and_then(|bytes| file::Binary::from_bytes(&bytes).ok());
+        assert!(
+            committed.is_some_and(|binary| binary.stack == parse(&example)),
+            "{compiled:?} doesn't match {example:?}, rebuild it with `BLESS=1 cargo test --test examples`."
+        );
+    }
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::*;
+use std::path::Path;
+use wasmi::{Caller, Engine, Linker, Module, Store};
+
+/// Runs the module with a host collecting the output, gives the output and whether it finished without trapping.
+fn run_module(bytes: &[u8]) -> (String, bool) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, bytes).unwrap();
+    let mut store = Store::new(&engine, Vec::<u8>::new());
+    let mut linker = Linker::<Vec<u8>>::new(&engine);
+
+    linker
+        .func_wrap(
+            "env",
+            "print",
+            |mut caller: Caller<'_, Vec<u8>>, ptr: i32, len: i32| {
+                let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+                let mut buffer = vec![0; len as usize];
+                memory.read(&caller, ptr as usize, &mut buffer).unwrap();
+                caller.data_mut().extend(buffer);
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "env",
+            "format_float",
+            |mut caller: Caller<'_, Vec<u8>>, value: f64, debug: i32, ptr: i32| -> i32 {
+                let formatted = if debug != 0 {
+                    format!("{value:?}")
+                } else {
+                    format!("{value}")
+                };
+                let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+                memory
+                    .write(&mut caller, ptr as usize, formatted.as_bytes())
+                    .unwrap();
+
+                formatted.len() as i32
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap("env", "pow", |base: f64, exponent: f64| base.powf(exponent))
+        .unwrap();
+
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let run = instance.get_typed_func::<(), ()>(&store, "run").unwrap();
+    let finished = run.call(&mut store, ()).is_ok();
+
+    (String::from_utf8(store.into_data()).unwrap(), finished)
+}
+
+fn assert_matches_interpreter(path: &Path, dir: &Path) {
+    let name = path.file_stem().unwrap().to_str().unwrap();
+    let module = dir.join(format!("{name}.wasm"));
+
+    let build = stack_lang(&[
+        "build",
+        "--target",
+        "wasm",
+        path.to_str().unwrap(),
+        "-o",
+        module.to_str().unwrap(),
+    ]);
+    assert!(build.status.success(), "{name} failed to compile");
+
+    let (output, finished) = run_module(&std::fs::read(module).unwrap());
+    assert!(finished, "{name} failed at runtime");
+
+    assert_eq!(
+        output,
+        interpreter_output(path),
+        "{name} prints something else than in the interpreter"
+    );
+}
+
+#[test]
+fn test_examples_match_interpreter() {
+    let dir = temp_dir("wasm_examples");
+
+    for example in examples() {
+        assert_matches_interpreter(&example, &dir);
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_numbers_match_interpreter() {
+    let dir = temp_dir("wasm_numbers");
+    let program = dir.join("numbers.sl");
+    std::fs::write(
+        &program,
+        "1.0 println 0.1 0.2 + println print_debug -0.0 println 1.5 2.0 ^ println\n\
+        7.5 2.0 % println -7.5 2.0 % println 100000000000000000000.0 3.0 % println 0.1 0.03 % println 5.5 -2.0 % println 1.0 3.0 / println 0.00009 print_debug\n\
+        1.0 0.0 / print_debug ! print_debug 0.0 0.0 / print_debug 2.0 1.0 < println\n\
+        -9223372036854775808 println 7 -2 / println 7 -2 % println 3 30 ^ println\n\
+        1 2 < 0 || println {\"yes\" println} 1 if print_debug_stack",
+    )
+    .unwrap();
+
+    assert_matches_interpreter(&program, &dir);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_runtime_error_traps() {
+    let dir = temp_dir("wasm_error");
+    let program = dir.join("error.sl");
+    std::fs::write(&program, "1 0 /").unwrap();
+    let module = dir.join("error.wasm");
+
+    let build = stack_lang(&[
+        "build",
+        "--target",
+        "wasm",
+        program.to_str().unwrap(),
+        "-o",
+        module.to_str().unwrap(),
+    ]);
+    assert!(build.status.success());
+
+    let (output, finished) = run_module(&std::fs::read(module).unwrap());
+    assert!(!finished);
+    assert!(output.ends_with("Runtime Error\u{1b}[0m\n\tAttempt to divide by zero.\n"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
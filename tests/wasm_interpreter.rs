@@ -0,0 +1,92 @@
+use std::{path::PathBuf, process::Command};
+use wasmi::{Engine, Linker, Module, Store};
+
+/**
+The interpreter built with `cargo build --lib --release --target wasm32-unknown-unknown`,
+or the module at `STACK_LANG_WASM` if it is set.
+
+It's built into a target directory of its own, the one of the running tests is locked by cargo.
+*/
+fn interpreter_module() -> Vec<u8> {
+    let path = match std::env::var_os("STACK_LANG_WASM") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let target_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("wasm_interpreter");
+            let status = Command::new(env!("CARGO"))
+                .args([
+                    "build",
+                    "--lib",
+                    "--release",
+                    "--target",
+                    "wasm32-unknown-unknown",
+                ])
+                .arg("--target-dir")
+                .arg(&target_dir)
+                .status()
+                .expect("Could not start cargo.");
+            assert!(
+                status.success(),
+                "Building the interpreter for wasm32-unknown-unknown failed, the target is added with `rustup target add wasm32-unknown-unknown`."
+            );
+
+            target_dir.join("wasm32-unknown-unknown/release/stack_lang_compiler.wasm")
+        }
+    };
+
+    std::fs::read(&path).unwrap_or_else(|err| panic!("Could not read {path:?}: {err}"))
+}
+
+#[test]
+fn test_eval_in_wasm_runtime() {
+    let bytes = interpreter_module();
+
+    let engine = Engine::default();
+    let module = Module::new(&engine, &bytes[..]).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Linker::<()>::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+
+    let memory = instance.get_memory(&store, "memory").unwrap();
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&store, "stack_lang_alloc")
+        .unwrap();
+    let eval = instance
+        .get_typed_func::<(i32, i32), i32>(&store, "eval")
+        .unwrap();
+    let free_result = instance
+        .get_typed_func::<i32, ()>(&store, "stack_lang_free_result")
+        .unwrap();
+    let free = instance
+        .get_typed_func::<(i32, i32), ()>(&store, "stack_lang_free")
+        .unwrap();
+
+    let source = b"2 3 + println 1 \"a\" +";
+    let ptr = alloc.call(&mut store, source.len() as i32).unwrap();
+    memory.write(&mut store, ptr as usize, source).unwrap();
+
+    let result = eval.call(&mut store, (ptr, source.len() as i32)).unwrap();
+    let mut fields = [0; 24];
+    memory.read(&store, result as usize, &mut fields).unwrap();
+
+    let read = |index: usize| {
+        let field =
+            |at: usize| u32::from_le_bytes(fields[at * 4..at * 4 + 4].try_into().unwrap()) as usize;
+        let mut text = vec![0; field(index * 2 + 1)];
+        memory.read(&store, field(index * 2), &mut text).unwrap();
+
+        String::from_utf8(text).unwrap()
+    };
+
+    assert_eq!(read(0), "5\n");
+    assert_eq!(read(1), "[Int(5)]");
+    assert_eq!(
+        read(2),
+        "Runtime Error at 1:21\n\tExpected Int but got Text(\"a\")."
+    );
+
+    free_result.call(&mut store, result).unwrap();
+    free.call(&mut store, (ptr, source.len() as i32)).unwrap();
+}
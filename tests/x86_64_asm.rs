@@ -0,0 +1,88 @@
+mod common;
+
+use common::*;
+use std::{path::Path, process::Command};
+
+#[test]
+fn test_examples_match_interpreter() {
+    if !is_installed("as") || !is_installed("ld") {
+        eprintln!("Skipping, `as` and `ld` are needed to assemble the output.");
+        return;
+    }
+
+    let dir = temp_dir("x86_64_asm");
+
+    for example in examples() {
+        let name = example.file_stem().unwrap().to_str().unwrap();
+
+        assert_eq!(
+            native_output(&example, &dir),
+            interpreter_output(&example),
+            "{name} prints something else than in the interpreter"
+        );
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_full_register_cache_matches_interpreter() {
+    if !is_installed("as") || !is_installed("ld") {
+        eprintln!("Skipping, `as` and `ld` are needed to assemble the output.");
+        return;
+    }
+
+    let dir = temp_dir("x86_64_asm_registers");
+    // `copy` with all six registers taken falls back to the routine, which flushes them at once.
+    let program = dir.join("registers.sl");
+    std::fs::write(
+        &program,
+        "1 2 3 4 5 6 copy 7 8 switch pop switch\n\
+         { println pop } 8 for\n\
+         1 2 3 4 5 6 7 copy + switch - 10 * println",
+    )
+    .unwrap();
+
+    assert_eq!(native_output(&program, &dir), interpreter_output(&program));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Output of the program compiled to x86-64 assembly, assembled and linked in `dir`.
+fn native_output(program: &Path, dir: &Path) -> String {
+    let name = program.file_stem().unwrap().to_str().unwrap();
+    let assembly = dir.join(format!("{name}.s"));
+    let object = dir.join(format!("{name}.o"));
+    let executable = dir.join(name);
+
+    let build = stack_lang(&[
+        "build",
+        "--target",
+        "x86_64-asm",
+        program.to_str().unwrap(),
+        "-o",
+        assembly.to_str().unwrap(),
+    ]);
+    assert!(build.status.success(), "{name} failed to compile");
+
+    let assembled = Command::new("as")
+        .arg(&assembly)
+        .arg("-o")
+        .arg(&object)
+        .status()
+        .unwrap();
+    assert!(assembled.success(), "{name} failed to assemble");
+
+    let linked = Command::new("ld")
+        .arg(&object)
+        .arg("-o")
+        .arg(&executable)
+        .status()
+        .unwrap();
+    assert!(linked.success(), "{name} failed to link");
+
+    let output = Command::new(&executable).output().unwrap();
+    assert!(output.status.success(), "{name} failed at runtime");
+
+    String::from_utf8(output.stdout).unwrap()
+}